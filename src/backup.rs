@@ -0,0 +1,613 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::config::{BackupSettings, NotifyEvent, RconSettings, ZSOConfig};
+use crate::notify::{self, Notification};
+use crate::rcon::RconClient;
+
+pub const MANIFEST_NAME: &str = "manifest.json";
+const ARCHIVE_EXTENSION: &str = ".tar.zst";
+/// The world counts as saved once nothing in it changed for this long.
+const SAVE_QUIET_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+}
+
+/// First entry of every archive.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub server_name: String,
+    pub created: i64,
+    pub reason: String,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub manifest: BackupManifest,
+    pub archive_size: u64,
+}
+
+/// What gets backed up, relative to the zomboid dir: the world, the player db and the server configs.
+fn backup_sources(zomboid_dir: &Path, server_name: &str) -> Vec<PathBuf> {
+    let mut sources = vec![
+        PathBuf::from("Saves/Multiplayer").join(server_name),
+        PathBuf::from("db").join(format!("{server_name}.db")),
+    ];
+
+    if let Ok(entries) = std::fs::read_dir(zomboid_dir.join("Server")) {
+        let mut server_files = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_owned))
+            .filter(|file_name| {
+                file_name.starts_with(&format!("{server_name}.")) || file_name.starts_with(&format!("{server_name}_"))
+            })
+            .map(|file_name| PathBuf::from("Server").join(file_name))
+            .collect::<Vec<PathBuf>>();
+        server_files.sort();
+        sources.extend(server_files);
+    }
+
+    sources
+}
+
+fn collect_files(zomboid_dir: &Path, relative: &Path, files: &mut Vec<ManifestEntry>) -> std::io::Result<()> {
+    let path = zomboid_dir.join(relative);
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if metadata.is_dir() {
+        let mut children = std::fs::read_dir(&path)?
+            .filter_map(Result::ok)
+            .map(|entry| relative.join(entry.file_name()))
+            .collect::<Vec<PathBuf>>();
+        children.sort();
+        for child in children {
+            collect_files(zomboid_dir, &child, files)?;
+        }
+    } else {
+        files.push(ManifestEntry {
+            path: relative.to_string_lossy().replace('\\', "/"),
+            size: metadata.len(),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn archive_name(server_name: &str, created: DateTime<Utc>) -> String {
+    format!("{}-{}{}", server_name, created.format("%Y%m%d-%H%M%S"), ARCHIVE_EXTENSION)
+}
+
+/// Two backups within the same second get a counter instead of replacing each other.
+fn unused_archive_path(dir: &Path, server_name: &str, created: DateTime<Utc>) -> PathBuf {
+    let name = archive_name(server_name, created);
+    let mut path = dir.join(&name);
+    let mut counter = 1;
+    while path.exists() {
+        let stem = name.trim_end_matches(ARCHIVE_EXTENSION);
+        path = dir.join(format!("{stem}-{counter}{ARCHIVE_EXTENSION}"));
+        counter += 1;
+    }
+    path
+}
+
+pub fn write_archive(
+    zomboid_dir: &Path,
+    server_name: &str,
+    archive_path: &Path,
+    reason: &str,
+    zstd_level: i32,
+) -> Result<BackupManifest, String> {
+    let mut manifest = BackupManifest {
+        server_name: server_name.to_owned(),
+        created: Utc::now().timestamp(),
+        reason: reason.to_owned(),
+        files: vec![],
+    };
+    for source in backup_sources(zomboid_dir, server_name) {
+        collect_files(zomboid_dir, &source, &mut manifest.files)
+            .map_err(|e| format!("failed to read {} - {}", source.display(), e))?;
+    }
+    if manifest.files.is_empty() {
+        return Err(format!("nothing to back up for {} in {}", server_name, zomboid_dir.display()));
+    }
+
+    let manifest_data = serde_json::to_vec_pretty(&manifest).map_err(|e| format!("failed to serialize manifest - {e}"))?;
+
+    // written next to the final name so a failed backup never looks like a finished one
+    let partial_path = archive_path.with_extension("partial");
+    let write = || -> std::io::Result<()> {
+        let file = File::create(&partial_path)?;
+        let mut encoder = zstd::Encoder::new(file, zstd_level)?;
+        encoder.include_checksum(true)?;
+
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(manifest.created.max(0) as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST_NAME, manifest_data.as_slice())?;
+
+        for entry in &manifest.files {
+            builder.append_path_with_name(zomboid_dir.join(&entry.path), &entry.path)?;
+        }
+
+        builder.into_inner()?.finish()?.sync_all()?;
+        std::fs::rename(&partial_path, archive_path)
+    };
+
+    match write() {
+        Ok(_) => Ok(manifest),
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial_path);
+            Err(format!("failed to write {} - {}", archive_path.display(), e))
+        }
+    }
+}
+
+fn open_archive(archive_path: &Path) -> Result<tar::Archive<zstd::Decoder<'static, std::io::BufReader<File>>>, String> {
+    let file = File::open(archive_path).map_err(|e| format!("failed to open {} - {}", archive_path.display(), e))?;
+    let decoder = zstd::Decoder::new(file).map_err(|e| format!("failed to read {} - {}", archive_path.display(), e))?;
+    Ok(tar::Archive::new(decoder))
+}
+
+pub fn read_manifest(archive_path: &Path) -> Result<BackupManifest, String> {
+    let mut archive = open_archive(archive_path)?;
+    let mut entries = archive.entries().map_err(|e| e.to_string())?;
+
+    let mut entry = match entries.next() {
+        Some(Ok(entry)) => entry,
+        _ => return Err(format!("{} is empty", archive_path.display())),
+    };
+    if entry.path().map(|path| path != Path::new(MANIFEST_NAME)).unwrap_or(true) {
+        return Err(format!("{} doesn't start with a manifest", archive_path.display()));
+    }
+
+    let mut manifest_data = String::new();
+    entry.read_to_string(&mut manifest_data).map_err(|e| e.to_string())?;
+    serde_json::from_str(&manifest_data).map_err(|e| format!("bad manifest in {} - {}", archive_path.display(), e))
+}
+
+/// Reads the whole archive, zstd checks its frame checksum on the way, and compares it with the manifest.
+pub fn verify_archive(archive_path: &Path) -> Result<BackupManifest, String> {
+    let manifest = read_manifest(archive_path)?;
+    let mut archive = open_archive(archive_path)?;
+    let mut found: Vec<ManifestEntry> = vec![];
+
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| format!("corrupt archive - {e}"))?;
+        let path = entry.path().map_err(|e| e.to_string())?.to_string_lossy().to_string();
+        let size = std::io::copy(&mut entry, &mut std::io::sink()).map_err(|e| format!("corrupt archive - {e}"))?;
+        if path != MANIFEST_NAME {
+            found.push(ManifestEntry { path, size });
+        }
+    }
+
+    for expected in &manifest.files {
+        match found.iter().find(|entry| entry.path == expected.path) {
+            Some(entry) if entry.size == expected.size => {}
+            Some(entry) => {
+                return Err(format!("{} is {} bytes, manifest says {}", entry.path, entry.size, expected.size));
+            }
+            None => return Err(format!("{} is missing", expected.path)),
+        }
+    }
+    if found.len() != manifest.files.len() {
+        return Err(format!("{} files in the archive, manifest lists {}", found.len(), manifest.files.len()));
+    }
+
+    Ok(manifest)
+}
+
+pub fn list_backups(settings: &BackupSettings) -> Vec<BackupInfo> {
+    let prefix = format!("{}-", settings.server_name);
+    let mut backups = vec![];
+
+    let entries = match std::fs::read_dir(&settings.dir) {
+        Ok(entries) => entries,
+        Err(_) => return backups,
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.starts_with(&prefix) || !file_name.ends_with(ARCHIVE_EXTENSION) {
+            continue;
+        }
+        match read_manifest(&entry.path()) {
+            Ok(manifest) => backups.push(BackupInfo {
+                path: entry.path(),
+                manifest,
+                archive_size: entry.metadata().map(|metadata| metadata.len()).unwrap_or_default(),
+            }),
+            Err(e) => warn!("Skipping {} - {}", entry.path().display(), e),
+        }
+    }
+
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.manifest.created));
+    backups
+}
+
+/// Backups to delete, `backups` newest first. The newest one always stays.
+pub fn expired_backups(backups: &[BackupInfo], keep_count: Option<usize>, keep_days: Option<u64>, now: i64) -> Vec<PathBuf> {
+    backups
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(index, backup)| {
+            let over_count = keep_count.is_some_and(|keep_count| *index >= keep_count);
+            let too_old = keep_days.is_some_and(|keep_days| now - backup.manifest.created > keep_days as i64 * 86400);
+            over_count || too_old
+        })
+        .map(|(_, backup)| backup.path.clone())
+        .collect()
+}
+
+fn newest_modification(path: &Path) -> Option<SystemTime> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mut newest = metadata.modified().ok();
+
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path).ok()?.filter_map(Result::ok) {
+            newest = newest.max(newest_modification(&entry.path()));
+        }
+    }
+
+    newest
+}
+
+/// RCON `save`, then waits until nothing in the save folder has changed for a few seconds.
+pub async fn save_world(rcon_settings: &RconSettings, save_dir: &Path, timeout: Duration) -> Result<(), String> {
+    RconClient::connect(rcon_settings).await?.exec("save").await?;
+
+    let started = tokio::time::Instant::now();
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let save_dir = save_dir.to_path_buf();
+        let newest = tokio::task::spawn_blocking(move || newest_modification(&save_dir))
+            .await
+            .unwrap_or_default();
+        let quiet = newest
+            .and_then(|newest| SystemTime::now().duration_since(newest).ok())
+            .is_none_or(|since| since >= SAVE_QUIET_PERIOD);
+
+        if quiet && started.elapsed() >= SAVE_QUIET_PERIOD {
+            return Ok(());
+        }
+        if started.elapsed() >= timeout {
+            return Err(format!("the world was still being written after {}s", timeout.as_secs()));
+        }
+    }
+}
+
+fn zomboid_dir(config: &ZSOConfig) -> Result<&Path, ()> {
+    match config
+        .server_settings
+        .as_ref()
+        .and_then(|server_settings| server_settings.zomboid_dir.as_deref())
+    {
+        Some(zomboid_dir) => Ok(zomboid_dir),
+        None => {
+            error!("server_settings.zomboid_dir is not set - can't find the world to back up");
+            Err(())
+        }
+    }
+}
+
+async fn write_backup(settings: &BackupSettings, zomboid_dir: &Path, reason: &str) -> Result<PathBuf, ()> {
+    if let Err(e) = tokio::fs::create_dir_all(&settings.dir).await {
+        error!("Failed to create {} - {}", settings.dir.display(), e);
+        return Err(());
+    }
+
+    let archive_path = unused_archive_path(&settings.dir, &settings.server_name, Utc::now());
+    let (zomboid_dir, server_name, reason, level, path) = (
+        zomboid_dir.to_path_buf(),
+        settings.server_name.clone(),
+        reason.to_owned(),
+        settings.zstd_level,
+        archive_path.clone(),
+    );
+
+    match tokio::task::spawn_blocking(move || write_archive(&zomboid_dir, &server_name, &path, &reason, level)).await {
+        Ok(Ok(manifest)) => {
+            info!("Backed up {} files to {}", manifest.files.len(), archive_path.display());
+            Ok(archive_path)
+        }
+        Ok(Err(e)) => {
+            error!("Backup failed - {}", e);
+            Err(())
+        }
+        Err(e) => {
+            error!("Backup failed - {}", e);
+            Err(())
+        }
+    }
+}
+
+/// Saves the world over RCON when it's reachable, archives it and applies the retention.
+pub async fn create_backup(config: &ZSOConfig, reason: &str) -> Result<PathBuf, ()> {
+    let archive_path = backup_world(config, reason).await;
+    if archive_path.is_err() {
+        let message = format!("The {reason} backup failed, see the zso log");
+        notify::notify(config, Notification::new(NotifyEvent::BackupFailed, "Backup failed", message)).await;
+    }
+    archive_path
+}
+
+async fn backup_world(config: &ZSOConfig, reason: &str) -> Result<PathBuf, ()> {
+    let settings = match &config.backup {
+        Some(settings) => settings,
+        None => {
+            error!("backup is not configured - add a backup section to the config");
+            return Err(());
+        }
+    };
+    let zomboid_dir = zomboid_dir(config)?;
+
+    if let Some(rcon_settings) = &config.rcon {
+        let save_dir = zomboid_dir.join("Saves/Multiplayer").join(&settings.server_name);
+        match save_world(rcon_settings, &save_dir, Duration::from_secs(settings.save_timeout_sec)).await {
+            Ok(_) => info!("World saved"),
+            Err(e) => warn!("Backing up without a fresh save - {}", e),
+        }
+    }
+
+    let archive_path = write_backup(settings, zomboid_dir, reason).await?;
+
+    let backups = list_backups(settings);
+    for expired in expired_backups(&backups, settings.keep_count, settings.keep_days, Utc::now().timestamp()) {
+        match tokio::fs::remove_file(&expired).await {
+            Ok(_) => info!("Deleted old backup {}", expired.display()),
+            Err(e) => warn!("Failed to delete old backup {} - {}", expired.display(), e),
+        }
+    }
+
+    Ok(archive_path)
+}
+
+/// Backs up when backups are configured, does nothing otherwise.
+pub async fn backup_if_configured(config: &ZSOConfig, reason: &str) -> Result<(), ()> {
+    match config.backup.is_some() {
+        true => create_backup(config, reason).await.map(|_| ()),
+        false => Ok(()),
+    }
+}
+
+/// Replaces the world with the archive's after taking a safety copy of the current one.
+/// The server has to be stopped unless `force` is set.
+pub async fn restore_backup(config: &ZSOConfig, archive_path: &Path, force: bool) -> Result<(), ()> {
+    let settings = match &config.backup {
+        Some(settings) => settings,
+        None => {
+            error!("backup is not configured - add a backup section to the config");
+            return Err(());
+        }
+    };
+    let zomboid_dir = zomboid_dir(config)?.to_path_buf();
+
+    let path = archive_path.to_path_buf();
+    let manifest = match tokio::task::spawn_blocking(move || verify_archive(&path)).await {
+        Ok(Ok(manifest)) => manifest,
+        Ok(Err(e)) => {
+            error!("Refusing to restore {} - {}", archive_path.display(), e);
+            return Err(());
+        }
+        Err(e) => {
+            error!("Failed to verify {} - {}", archive_path.display(), e);
+            return Err(());
+        }
+    };
+
+    if !force {
+        match &config.rcon {
+            Some(rcon_settings) => {
+                if RconClient::connect(rcon_settings).await.is_ok() {
+                    error!("The server is running - stop it before restoring, or pass --force");
+                    return Err(());
+                }
+            }
+            None => {
+                error!("RCON is not configured, so zso can't tell whether the server is running - stop it and pass --force");
+                return Err(());
+            }
+        }
+    }
+
+    let reason = format!("safety copy before restoring {}", archive_path.display());
+    let safety_copy = write_backup(settings, &zomboid_dir, &reason).await?;
+    info!("Safety copy of the current world is {}", safety_copy.display());
+
+    let path = archive_path.to_path_buf();
+    let server_name = manifest.server_name.clone();
+    let restore = move || -> Result<(), String> {
+        let save_dir = zomboid_dir.join("Saves/Multiplayer").join(&server_name);
+        if save_dir.exists() {
+            std::fs::remove_dir_all(&save_dir).map_err(|e| format!("failed to remove {} - {}", save_dir.display(), e))?;
+        }
+
+        let mut archive = open_archive(&path)?;
+        for entry in archive.entries().map_err(|e| e.to_string())? {
+            let mut entry = entry.map_err(|e| e.to_string())?;
+            if entry.path().map(|path| path == Path::new(MANIFEST_NAME)).unwrap_or(false) {
+                continue;
+            }
+            entry.unpack_in(&zomboid_dir).map_err(|e| format!("failed to extract - {e}"))?;
+        }
+        Ok(())
+    };
+
+    match tokio::task::spawn_blocking(restore).await {
+        Ok(Ok(_)) => {
+            info!(
+                "Restored {} files from {} ({})",
+                manifest.files.len(),
+                archive_path.display(),
+                manifest.reason
+            );
+            Ok(())
+        }
+        Ok(Err(e)) => {
+            error!("Restore failed - {}, the safety copy is {}", e, safety_copy.display());
+            Err(())
+        }
+        Err(e) => {
+            error!("Restore failed - {}, the safety copy is {}", e, safety_copy.display());
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerSettings;
+    use crate::rcon::tests::fake_server;
+
+    #[test]
+    fn archive_round_trip_test() {
+        let test_dir = std::env::temp_dir().join(format!("zso-backup-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        let zomboid_dir = test_dir.join("Zomboid");
+        std::fs::create_dir_all(zomboid_dir.join("Saves/Multiplayer/pz/chunkdata")).unwrap();
+        std::fs::create_dir_all(zomboid_dir.join("db")).unwrap();
+        std::fs::create_dir_all(zomboid_dir.join("Server")).unwrap();
+        std::fs::write(zomboid_dir.join("Saves/Multiplayer/pz/map_t.bin"), vec![7; 4096]).unwrap();
+        std::fs::write(zomboid_dir.join("Saves/Multiplayer/pz/chunkdata/chunkdata_1_1.bin"), b"chunk").unwrap();
+        std::fs::write(zomboid_dir.join("db/pz.db"), b"players").unwrap();
+        std::fs::write(zomboid_dir.join("Server/pz.ini"), b"Mods=a").unwrap();
+        std::fs::write(zomboid_dir.join("Server/pzother.ini"), b"Mods=b").unwrap();
+
+        let archive_path = test_dir.join("pz.tar.zst");
+        let manifest = write_archive(&zomboid_dir, "pz", &archive_path, "test", 3).unwrap();
+        let verified = verify_archive(&archive_path);
+
+        // flip a byte in the middle of the compressed data
+        let mut corrupted = std::fs::read(&archive_path).unwrap();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0xff;
+        std::fs::write(test_dir.join("corrupted.tar.zst"), corrupted).unwrap();
+        let corrupted = verify_archive(&test_dir.join("corrupted.tar.zst"));
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert_eq!(
+            vec![
+                "Saves/Multiplayer/pz/chunkdata/chunkdata_1_1.bin",
+                "Saves/Multiplayer/pz/map_t.bin",
+                "db/pz.db",
+                "Server/pz.ini",
+            ],
+            manifest.files.iter().map(|entry| entry.path.as_str()).collect::<Vec<&str>>()
+        );
+        assert_eq!(Ok(manifest), verified);
+        assert!(corrupted.is_err());
+    }
+
+    #[test]
+    fn retention_test() {
+        let day = 86400;
+        let backup = |name: &str, created: i64| BackupInfo {
+            path: PathBuf::from(name),
+            manifest: BackupManifest {
+                created,
+                ..Default::default()
+            },
+            archive_size: 0,
+        };
+        let backups = vec![
+            backup("a", 10 * day),
+            backup("b", 9 * day),
+            backup("c", 5 * day),
+            backup("d", day),
+        ];
+
+        assert_eq!(
+            vec![PathBuf::from("c"), PathBuf::from("d")],
+            expired_backups(&backups, Some(5), Some(3), 10 * day)
+        );
+        assert_eq!(
+            vec![PathBuf::from("d")],
+            expired_backups(&backups, Some(3), None, 10 * day)
+        );
+        // the newest backup stays even when it's too old
+        assert!(expired_backups(&backups[..1], None, Some(1), 100 * day).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn save_world_test() {
+        let rcon_settings = fake_server(0).await;
+        let save_dir = std::env::temp_dir().join(format!("zso-save-world-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&save_dir);
+        std::fs::create_dir_all(save_dir.join("chunkdata")).unwrap();
+
+        // written a minute ago, quiet as soon as the quiet period passed
+        File::create(save_dir.join("chunkdata/chunkdata_1_1.bin")).unwrap();
+        for path in ["chunkdata/chunkdata_1_1.bin", "chunkdata", ""] {
+            let file = File::open(save_dir.join(path)).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(60)).unwrap();
+        }
+        let started = tokio::time::Instant::now();
+        let saved = save_world(&rcon_settings, &save_dir, Duration::from_secs(30)).await;
+        let waited = started.elapsed();
+
+        // the paused clock doesn't move SystemTime, a file written just now never goes quiet
+        std::fs::write(save_dir.join("map_t.bin"), b"map").unwrap();
+        let still_writing = save_world(&rcon_settings, &save_dir, Duration::from_secs(30)).await;
+        let _ = std::fs::remove_dir_all(&save_dir);
+
+        assert_eq!(Ok(()), saved);
+        assert_eq!(SAVE_QUIET_PERIOD, waited);
+        assert_eq!(Err("the world was still being written after 30s".to_owned()), still_writing);
+    }
+
+    #[tokio::test]
+    async fn restore_needs_force_without_rcon_test() {
+        let test_dir = std::env::temp_dir().join(format!("zso-restore-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        let zomboid_dir = test_dir.join("Zomboid");
+        std::fs::create_dir_all(zomboid_dir.join("Saves/Multiplayer/pz")).unwrap();
+        std::fs::write(zomboid_dir.join("Saves/Multiplayer/pz/map_t.bin"), b"old").unwrap();
+        let archive_path = test_dir.join("pz.tar.zst");
+        write_archive(&zomboid_dir, "pz", &archive_path, "test", 3).unwrap();
+        std::fs::write(zomboid_dir.join("Saves/Multiplayer/pz/map_t.bin"), b"new").unwrap();
+
+        let config = ZSOConfig {
+            server_settings: Some(ServerSettings {
+                zomboid_dir: Some(zomboid_dir.clone()),
+                ..Default::default()
+            }),
+            backup: Some(BackupSettings {
+                dir: test_dir.join("backups"),
+                server_name: "pz".to_owned(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let refused = restore_backup(&config, &archive_path, false).await;
+        let kept = std::fs::read(zomboid_dir.join("Saves/Multiplayer/pz/map_t.bin")).unwrap();
+        let forced = restore_backup(&config, &archive_path, true).await;
+        let restored = std::fs::read(zomboid_dir.join("Saves/Multiplayer/pz/map_t.bin")).unwrap();
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert_eq!(Err(()), refused);
+        assert_eq!(b"new".to_vec(), kept);
+        assert_eq!(Ok(()), forced);
+        assert_eq!(b"old".to_vec(), restored);
+    }
+}
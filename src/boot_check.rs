@@ -0,0 +1,339 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+
+use crate::config::{NotifyEvent, ServerSettings, ZSOConfig};
+use crate::log_parser::{LogTailer, ServerEvent};
+use crate::notify::{self, Notification};
+use crate::operator;
+use crate::rcon::RconClient;
+use crate::reboot_messages::RebootNotice;
+
+/// Why a server didn't come up healthy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootFailure {
+    pub reason: String,
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for BootFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)?;
+        for error in &self.errors {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects what the server logs while it starts. Mod errors fail the boot even if the server comes up anyway.
+#[derive(Debug, Default)]
+pub struct BootWatch {
+    mod_errors: Vec<String>,
+    exceptions: Vec<String>,
+}
+
+impl BootWatch {
+    /// `Some` once the server reported it's started.
+    pub fn observe(&mut self, event: &ServerEvent) -> Option<Result<(), BootFailure>> {
+        match event {
+            ServerEvent::ModLoadError { message, .. } => self.mod_errors.push(message.clone()),
+            ServerEvent::CrashStackTrace { exception, .. } => self.exceptions.push(exception.clone()),
+            ServerEvent::ServerStarted => {
+                return Some(match self.mod_errors.is_empty() {
+                    true => Ok(()),
+                    false => Err(BootFailure {
+                        reason: "the server started with mod errors".to_owned(),
+                        errors: self.mod_errors.clone(),
+                    }),
+                });
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn timed_out(&self, timeout: Duration) -> BootFailure {
+        BootFailure {
+            reason: format!("the server didn't start within {}s", timeout.as_secs()),
+            errors: self.mod_errors.iter().chain(&self.exceptions).cloned().collect(),
+        }
+    }
+}
+
+/// Follows the logs until the server reports it's started.
+pub async fn wait_for_log(tailer: &mut LogTailer, timeout: Duration) -> Result<(), BootFailure> {
+    let started = Instant::now();
+    let mut watch = BootWatch::default();
+
+    while started.elapsed() < timeout {
+        for event in tailer.poll().await {
+            if let Some(outcome) = watch.observe(&event) {
+                return outcome;
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    Err(watch.timed_out(timeout))
+}
+
+/// Without logs to follow, a server answering RCON counts as started.
+/// Answers only count once RCON was seen down, the old server may still be shutting down.
+async fn wait_for_rcon(config: &ZSOConfig, timeout: Duration) -> Result<(), BootFailure> {
+    let rcon_settings = match &config.rcon {
+        Some(rcon_settings) => rcon_settings,
+        None => {
+            return Err(BootFailure {
+                reason: "neither server_settings.zomboid_dir nor rcon is set - can't tell whether the server started"
+                    .to_owned(),
+                errors: vec![],
+            })
+        }
+    };
+
+    let started = tokio::time::Instant::now();
+    let mut last_error = None;
+    while started.elapsed() < timeout {
+        match RconClient::connect(rcon_settings).await {
+            Ok(_) if last_error.is_some() => return Ok(()),
+            Ok(_) => {}
+            Err(e) => last_error = Some(e),
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+
+    Err(match last_error {
+        Some(last_error) => BootFailure {
+            reason: format!("the server didn't answer RCON within {}s", timeout.as_secs()),
+            errors: vec![last_error],
+        },
+        None => BootFailure {
+            reason: format!("RCON never went down within {}s, the old server is still running", timeout.as_secs()),
+            errors: vec![],
+        },
+    })
+}
+
+/// Set up before rebooting so nothing the new server logs is missed.
+async fn boot_tailer(server_settings: &ServerSettings) -> Option<LogTailer> {
+    let mut tailer = LogTailer::new(server_settings.zomboid_dir.as_deref()?, false);
+    // skips what the running server already logged
+    tailer.poll().await;
+    Some(tailer)
+}
+
+async fn wait_for_boot(config: &ZSOConfig, tailer: Option<LogTailer>, timeout: Duration) -> Result<(), BootFailure> {
+    match tailer {
+        Some(mut tailer) => wait_for_log(&mut tailer, timeout).await,
+        None => wait_for_rcon(config, timeout).await,
+    }
+}
+
+/// Files an ini change rewrites, as they were before it. `None` for files that didn't exist.
+pub struct ConfigSnapshot {
+    files: Vec<(PathBuf, Option<Vec<u8>>)>,
+}
+
+impl ConfigSnapshot {
+    pub async fn take(paths: &[PathBuf]) -> Result<Self, ()> {
+        let mut files = vec![];
+
+        for path in paths {
+            match tokio::fs::read(path).await {
+                Ok(contents) => files.push((path.clone(), Some(contents))),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => files.push((path.clone(), None)),
+                Err(e) => {
+                    error!("Failed to read {} - {}", path.display(), e);
+                    return Err(());
+                }
+            }
+        }
+
+        Ok(Self { files })
+    }
+
+    pub async fn changed(&self) -> bool {
+        for (path, contents) in &self.files {
+            if tokio::fs::read(path).await.ok() != *contents {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub async fn restore(&self) -> Result<(), ()> {
+        let mut result = Ok(());
+
+        for (path, contents) in &self.files {
+            let restored = match contents {
+                Some(contents) => tokio::fs::write(path, contents).await,
+                None => match tokio::fs::remove_file(path).await {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                    removed => removed,
+                },
+            };
+
+            match restored {
+                Ok(_) => info!("Restored {}", path.display()),
+                Err(e) => {
+                    error!("Failed to restore {} - {}", path.display(), e);
+                    result = Err(());
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Reboots the server and waits for a healthy start. A failed start puts `snapshot` back and reboots again,
+/// `Err` means the change was rolled back or couldn't be applied.
+pub async fn restart_or_roll_back(
+    config: &ZSOConfig,
+    server_settings: &ServerSettings,
+    snapshot: &ConfigSnapshot,
+) -> Result<(), ()> {
+    let timeout = Duration::from_secs(server_settings.boot_timeout_sec);
+
+    let tailer = boot_tailer(server_settings).await;
+    operator::reboot_server(config, server_settings, &RebootNotice::new("config_change"), &mut None).await?;
+    let failure = match wait_for_boot(config, tailer, timeout).await {
+        Ok(_) => {
+            info!("Server started with the new config");
+            return Ok(());
+        }
+        Err(failure) => failure,
+    };
+
+    error!("Server failed to start after the ini change - {}", failure);
+    if snapshot.restore().await.is_err() {
+        error!("Rolling back the ini change failed - the server needs an admin");
+        let message = format!("{}, and putting the previous config back failed", failure.reason);
+        notify::notify_in_background(config, Notification::new(NotifyEvent::Rollback, "Rollback failed", message).details(&failure.errors));
+        return Err(());
+    }
+
+    // players were already kicked by the first reboot, no countdown this time
+    info!("Rolled back the ini change, restarting the server");
+    let tailer = boot_tailer(server_settings).await;
+    operator::run_reboot_command(config, server_settings).await?;
+    let message = match wait_for_boot(config, tailer, timeout).await {
+        Ok(_) => {
+            info!("Server is back up with the previous config");
+            format!("{}, the server is back up with the previous config", failure.reason)
+        }
+        Err(rollback_failure) => {
+            error!("Server failed to start after the rollback too - {}", rollback_failure);
+            format!("{}, and it didn't start with the previous config either", failure.reason)
+        }
+    };
+    notify::notify_in_background(config, Notification::new(NotifyEvent::Rollback, "Ini change rolled back", message).details(&failure.errors));
+
+    Err(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RconSettings;
+    use crate::rcon::tests::{fake_server, fake_server_on};
+
+    #[test]
+    fn boot_watch_test() {
+        let mod_error = ServerEvent::ModLoadError {
+            mod_id: Some("Brita".to_owned()),
+            message: "required mod \"Brita\" not found".to_owned(),
+        };
+        let crash = ServerEvent::CrashStackTrace {
+            exception: "java.lang.NullPointerException".to_owned(),
+            trace: vec![],
+        };
+
+        let mut healthy = BootWatch::default();
+        assert_eq!(None, healthy.observe(&crash));
+        assert_eq!(Some(Ok(())), healthy.observe(&ServerEvent::ServerStarted));
+
+        let mut broken = BootWatch::default();
+        assert_eq!(None, broken.observe(&mod_error));
+        assert_eq!(
+            Some(Err(BootFailure {
+                reason: "the server started with mod errors".to_owned(),
+                errors: vec!["required mod \"Brita\" not found".to_owned()],
+            })),
+            broken.observe(&ServerEvent::ServerStarted)
+        );
+
+        let mut stuck = BootWatch::default();
+        stuck.observe(&mod_error);
+        stuck.observe(&crash);
+        assert_eq!(2, stuck.timed_out(Duration::from_secs(600)).errors.len());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_rcon_test() {
+        let timeout = Duration::from_secs(60);
+
+        // the old server never went away
+        let config = ZSOConfig {
+            rcon: Some(fake_server(0).await),
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(BootFailure {
+                reason: "RCON never went down within 60s, the old server is still running".to_owned(),
+                errors: vec![],
+            }),
+            wait_for_rcon(&config, timeout).await
+        );
+
+        // down first, then the new server answers
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+        let config = ZSOConfig {
+            rcon: Some(RconSettings {
+                host: "127.0.0.1".to_owned(),
+                port: port.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(20)).await;
+            fake_server_on(tokio::net::TcpListener::bind(("127.0.0.1", port)).await.unwrap(), 0);
+        });
+        let started = tokio::time::Instant::now();
+        assert_eq!(Ok(()), wait_for_rcon(&config, timeout).await);
+        assert!(started.elapsed() >= Duration::from_secs(20));
+    }
+
+    #[tokio::test]
+    async fn config_snapshot_test() {
+        let test_dir = std::env::temp_dir().join(format!("zso-config-snapshot-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let ini = test_dir.join("pz.ini");
+        let spawnregions = test_dir.join("pz_spawnregions.lua");
+        std::fs::write(&ini, "Mods=Brita").unwrap();
+
+        let snapshot = ConfigSnapshot::take(&[ini.clone(), spawnregions.clone()]).await.unwrap();
+        let unchanged = snapshot.changed().await;
+        std::fs::write(&ini, "Mods=Brita;Arsenal").unwrap();
+        std::fs::write(&spawnregions, "function SpawnRegions() end").unwrap();
+        let changed = snapshot.changed().await;
+        let restored = snapshot.restore().await;
+
+        let ini_contents = std::fs::read_to_string(&ini).unwrap();
+        let spawnregions_exists = spawnregions.exists();
+        let changed_after_restore = snapshot.changed().await;
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert!(!unchanged);
+        assert!(changed);
+        assert_eq!(Ok(()), restored);
+        assert_eq!("Mods=Brita", ini_contents);
+        // didn't exist before the change, so it's removed again
+        assert!(!spawnregions_exists);
+        assert!(!changed_after_restore);
+    }
+}
@@ -1,14 +1,30 @@
 
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 
+use crate::sandbox_vars::SandboxValue;
+use crate::server_ini::IniValue;
+
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ZSOConfig {
     pub collections: Vec<u64>,
     pub workshop_settings: ConfigWorkshopSettings,
     pub rcon: Option<RconSettings>,
-    pub server_settings: Option<ServerSettings>
+    pub server_settings: Option<ServerSettings>,
+    pub sandbox: Option<SandboxSettings>,
+    /// Server ini options to set on apply, e.g. `MaxPlayers: 32`.
+    #[serde(default)]
+    pub server_options: BTreeMap<String, IniValue>,
+    pub steamcmd: Option<SteamCmdSettings>,
+    pub supervisor: Option<SupervisorSettings>,
+    pub schedule: Option<ScheduleSettings>,
+    pub backup: Option<BackupSettings>,
+    pub staging: Option<StagingSettings>,
+    pub notifications: Option<NotificationSettings>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -16,6 +32,135 @@ pub struct ServerSettings {
     pub reboot_command: String,
     pub reboot_delay_sec: u64,
     pub rcon_messages: bool,
+    /// Dedicated server install folder, the one with `start-server.sh` and `media/`.
+    #[serde(default)]
+    pub install_dir: Option<PathBuf>,
+    /// Server user folder, the one with `server-console.txt`, `Logs/`, `Saves/` and `db/`. Usually `~/Zomboid`.
+    #[serde(default)]
+    pub zomboid_dir: Option<PathBuf>,
+    /// Where `watch` gets the latest dedicated server build from.
+    #[serde(default)]
+    pub build_source: BuildSource,
+    /// How often `watch` looks for mod and server updates.
+    #[serde(default = "default_check_interval_sec")]
+    pub check_interval_sec: u64,
+    /// Outdated workshop item reports in the server log are ignored this long after a reboot.
+    #[serde(default = "default_out_of_date_debounce_sec")]
+    pub out_of_date_debounce_sec: u64,
+    /// What to do when players are online, an empty server reboots right away.
+    #[serde(default)]
+    pub reboot_policy: RebootPolicy,
+    /// Updates detected within this long of the first one are handled with a single reboot.
+    #[serde(default = "default_update_debounce_sec")]
+    pub update_debounce_sec: u64,
+    /// Only reboot for updates inside these windows.
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceSettings>,
+    /// How long a restart after an ini change may take before it's rolled back.
+    #[serde(default = "default_boot_timeout_sec")]
+    pub boot_timeout_sec: u64,
+}
+
+fn default_check_interval_sec() -> u64 {
+    600
+}
+
+fn default_out_of_date_debounce_sec() -> u64 {
+    1800
+}
+
+fn default_update_debounce_sec() -> u64 {
+    300
+}
+
+fn default_boot_timeout_sec() -> u64 {
+    600
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceSettings {
+    /// IANA name, e.g. `Europe/Berlin`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub windows: Vec<MaintenanceWindow>,
+    /// Reboot outside the windows when the update keeps players from joining:
+    /// a new dedicated server build or the server reporting an outdated workshop item.
+    #[serde(default = "default_emergency_override")]
+    pub emergency_override: bool,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_owned()
+}
+
+fn default_emergency_override() -> bool {
+    true
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceWindow {
+    /// `mon`, `tue`, ... every day when empty.
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// `HH:MM`, a window ending before it starts runs past midnight.
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RebootPolicy {
+    /// Always run the full countdown.
+    #[default]
+    Countdown,
+    /// Wait for the last player to leave, then run the countdown once `max_delay_sec` is over.
+    WaitForEmpty { max_delay_sec: u64 },
+    /// Players can postpone the reboot by typing `command` in chat, more than half of them have to.
+    Vote {
+        #[serde(default = "default_vote_command")]
+        command: String,
+        #[serde(default = "default_postpone_sec")]
+        postpone_sec: u64,
+        #[serde(default = "default_max_postpones")]
+        max_postpones: u32,
+        #[serde(default = "default_vote_message")]
+        vote_message: String,
+        #[serde(default = "default_postponed_message")]
+        postponed_message: String,
+    },
+}
+
+fn default_vote_command() -> String {
+    "!postpone".to_owned()
+}
+
+fn default_postpone_sec() -> u64 {
+    1800
+}
+
+fn default_max_postpones() -> u32 {
+    1
+}
+
+fn default_vote_message() -> String {
+    "Type !postpone in chat to vote for postponing the reboot".to_owned()
+}
+
+fn default_postponed_message() -> String {
+    "Players voted to postpone the reboot".to_owned()
+}
+
+/// Source of the latest dedicated server build id.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BuildSource {
+    /// `app_info_print` through the configured steamcmd.
+    #[default]
+    SteamCmd,
+    /// Fixed build id, for trying out the update pipeline.
+    Stub { build_id: u64 },
+    /// Don't check for dedicated server updates.
+    Disabled,
 }
 
 
@@ -29,15 +174,70 @@ pub struct RconSettings {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RconMessagesSettings {
+    #[serde(default)]
     pub reboot_15m: String,
+    #[serde(default)]
     pub reboot_5m: String,
+    #[serde(default)]
     pub reboot_1m: String,
+    /// Replaces the three fixed messages when set. Messages are templates that can use
+    /// `minutes`, `reason`, `reasons`, `reason_keys`, `mods` and `downtime`.
+    #[serde(default)]
+    pub countdown: Vec<CountdownStep>,
+    /// Wording of the reboot reasons by key, e.g. `mod_update: {en: mod updates, de: Mod-Updates}`.
+    /// Keys are `mod_update`, `game_update`, `outdated_mod`, `scheduled` and `config_change`.
+    #[serde(default)]
+    pub reasons: BTreeMap<String, LocalizedMessage>,
+    /// Broadcast order of translated messages, every translation in name order when empty.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Expected downtime in minutes, for the `downtime` variable.
+    #[serde(default = "default_downtime_min")]
+    pub downtime_min: u64,
+}
+
+fn default_downtime_min() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountdownStep {
+    /// Minutes before the reboot this message goes out.
+    pub minutes: u64,
+    pub message: LocalizedMessage,
+}
+
+/// A single message, or one per language, e.g. `{en: ..., de: ...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LocalizedMessage {
+    Text(String),
+    Languages(BTreeMap<String, String>),
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConfigWorkshopSettings {
     pub include: IncludeExcludeStruct,
-    pub exclude: IncludeExcludeStruct
+    pub exclude: IncludeExcludeStruct,
+    #[serde(default)]
+    pub conflicts: ConflictSettings,
+    /// Downloaded workshop content, usually `steamapps/workshop/content/108600`.
+    #[serde(default)]
+    pub content_dir: Option<PathBuf>,
+}
+
+/// Picks the workshop item that wins when several items declare the same Mod ID or map folder.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflictSettings {
+    /// Mod ID -> workshop item id that should provide it.
+    #[serde(default)]
+    pub mods: BTreeMap<String, u64>,
+    /// Map folder -> workshop item id that should provide it.
+    #[serde(default)]
+    pub maps: BTreeMap<String, u64>,
+    /// Refuse to update the server ini while any conflict is left unresolved.
+    #[serde(default)]
+    pub fail_on_unresolved: bool,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -47,3 +247,297 @@ pub struct IncludeExcludeStruct {
     pub maps: Vec<String>,
 }
 
+
+/// Desired `SandboxVars.lua` values. Keys are dotted paths, e.g. `ZombieLore.Speed`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SandboxSettings {
+    /// Defaults to `<server>_SandboxVars.lua` next to the server ini.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Name of the preset from `presets` to apply.
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub presets: BTreeMap<String, BTreeMap<String, SandboxValue>>,
+    /// Applied on top of the preset.
+    #[serde(default)]
+    pub values: BTreeMap<String, SandboxValue>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SteamCmdSettings {
+    /// steamcmd binary or `steamcmd.sh`.
+    pub path: PathBuf,
+    /// Passed as `+force_install_dir`, workshop content ends up in `steamapps/workshop/content/108600` below it.
+    #[serde(default)]
+    pub install_dir: Option<PathBuf>,
+    #[serde(default = "default_steamcmd_login")]
+    pub login: String,
+    #[serde(default = "default_steamcmd_retries")]
+    pub retries: u32,
+    /// Grows linearly with every retry.
+    #[serde(default = "default_steamcmd_retry_delay_sec")]
+    pub retry_delay_sec: u64,
+}
+
+fn default_steamcmd_login() -> String {
+    "anonymous".to_owned()
+}
+
+fn default_steamcmd_retries() -> u32 {
+    3
+}
+
+fn default_steamcmd_retry_delay_sec() -> u64 {
+    10
+}
+
+/// `supervise` mode: zso runs the server itself instead of relying on `reboot_command`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SupervisorSettings {
+    /// Defaults to `start-server.sh` in `server_settings.install_dir`.
+    #[serde(default)]
+    pub start_script: Option<PathBuf>,
+    /// Passed to the start script, e.g. `["-servername", "pzserver"]`.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Handed to the JVM through `JAVA_TOOL_OPTIONS`, e.g. `-Xmx8g`.
+    #[serde(default)]
+    pub jvm_options: Vec<String>,
+    /// Server stdout and stderr end up in `server.log` here.
+    pub log_dir: PathBuf,
+    #[serde(default = "default_log_max_bytes")]
+    pub log_max_bytes: u64,
+    /// Rotated logs to keep next to `server.log`.
+    #[serde(default = "default_log_files")]
+    pub log_files: u32,
+    /// Delay before the first restart after a crash, doubled for every crash after it.
+    #[serde(default = "default_restart_delay_sec")]
+    pub restart_delay_sec: u64,
+    #[serde(default = "default_max_restart_delay_sec")]
+    pub max_restart_delay_sec: u64,
+    /// Give up after this many crashes within `crash_loop_window_sec`.
+    #[serde(default = "default_crash_loop_count")]
+    pub crash_loop_count: u32,
+    #[serde(default = "default_crash_loop_window_sec")]
+    pub crash_loop_window_sec: u64,
+    /// Exits sooner than this after starting count as crashes, clean ones too.
+    #[serde(default = "default_min_uptime_sec")]
+    pub min_uptime_sec: u64,
+    /// How long each stop step (RCON quit, SIGTERM) gets before the next one.
+    #[serde(default = "default_stop_timeout_sec")]
+    pub stop_timeout_sec: u64,
+}
+
+fn default_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_files() -> u32 {
+    5
+}
+
+fn default_restart_delay_sec() -> u64 {
+    5
+}
+
+fn default_max_restart_delay_sec() -> u64 {
+    300
+}
+
+fn default_crash_loop_count() -> u32 {
+    5
+}
+
+fn default_crash_loop_window_sec() -> u64 {
+    600
+}
+
+fn default_min_uptime_sec() -> u64 {
+    60
+}
+
+fn default_stop_timeout_sec() -> u64 {
+    60
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleSettings {
+    /// IANA name the cron expressions are evaluated in, e.g. `Europe/Berlin`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    pub tasks: Vec<ScheduledTask>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTask {
+    pub name: String,
+    /// `min hour day month weekday`, or with seconds in front.
+    pub cron: String,
+    pub action: TaskAction,
+    /// Run once on start when zso wasn't running at the last fire time.
+    #[serde(default)]
+    pub run_missed: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskAction {
+    RconCommand {
+        command: String,
+    },
+    Broadcast {
+        message: String,
+    },
+    /// Reboot following `server_settings.reboot_policy`.
+    #[default]
+    RebootCountdown,
+    Backup,
+    /// Resolve the collections again and update the server ini.
+    Refresh {
+        ini: PathBuf,
+        #[serde(default)]
+        maps: bool,
+        /// Accept workshop changes that zso.lock doesn't cover.
+        #[serde(default)]
+        update: bool,
+    },
+}
+
+/// World backups of `server_settings.zomboid_dir`, taken before reboots and mod changes.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackupSettings {
+    pub dir: PathBuf,
+    /// `-servername` of the server, names the save folder, the player db and the ini.
+    #[serde(default = "default_server_name")]
+    pub server_name: String,
+    /// Keep at most this many backups.
+    #[serde(default)]
+    pub keep_count: Option<usize>,
+    /// Delete backups older than this, the newest one is always kept.
+    #[serde(default)]
+    pub keep_days: Option<u64>,
+    #[serde(default = "default_zstd_level")]
+    pub zstd_level: i32,
+    /// How long to wait for the world to be written after RCON `save`.
+    #[serde(default = "default_save_timeout_sec")]
+    pub save_timeout_sec: u64,
+}
+
+fn default_server_name() -> String {
+    "servertest".to_owned()
+}
+
+fn default_zstd_level() -> i32 {
+    3
+}
+
+fn default_save_timeout_sec() -> u64 {
+    120
+}
+
+/// Throwaway server that has to boot the new mod set before the real ini is changed.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StagingSettings {
+    /// The staging server's Zomboid folder is kept in here and recreated on every run.
+    pub dir: PathBuf,
+    /// Defaults to `supervisor.start_script`, then `start-server.sh` in `server_settings.install_dir`.
+    #[serde(default)]
+    pub start_script: Option<PathBuf>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Defaults to `supervisor.jvm_options`.
+    #[serde(default)]
+    pub jvm_options: Vec<String>,
+    /// `DefaultPort` of the staging server, `UDPPort` and `RCONPort` take the next two.
+    #[serde(default = "default_staging_port")]
+    pub port: u16,
+    #[serde(default = "default_boot_timeout_sec")]
+    pub boot_timeout_sec: u64,
+}
+
+fn default_staging_port() -> u16 {
+    16300
+}
+
+/// Where operator events go besides the log.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    pub sinks: Vec<NotificationSink>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    /// Mod or dedicated server updates were found.
+    Update,
+    Reboot,
+    RebootFailed,
+    /// An ini change kept the server from starting and was rolled back.
+    Rollback,
+    PreflightFailed,
+    BackupFailed,
+    /// The supervised server crashed.
+    Crash,
+    TaskFailed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationSink {
+    #[serde(flatten)]
+    pub target: SinkTarget,
+    /// Events this sink gets, every event when empty.
+    #[serde(default)]
+    pub events: Vec<NotifyEvent>,
+    /// Can use `event`, `title`, `message`, `details` and `time`.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkTarget {
+    Discord {
+        url: String,
+    },
+    Slack {
+        url: String,
+    },
+    /// POSTs the notification as JSON, the rendered template is in `text`.
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    Email {
+        host: String,
+        #[serde(default = "default_smtp_port")]
+        port: u16,
+        #[serde(default)]
+        tls: SmtpTls,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+    /// Runs with `sh -c`, gets the rendered template on stdin and `ZSO_EVENT`, `ZSO_TITLE` and `ZSO_MESSAGE` set.
+    Command {
+        command: String,
+    },
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTls {
+    #[default]
+    Starttls,
+    /// Implicit TLS, usually port 465.
+    Tls,
+    None,
+}
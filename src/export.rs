@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use crate::steam_api_client::ModData;
+use crate::template::{Template, TemplateContext, TemplateValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// `.env` file with WORKSHOP_IDS and MOD_IDS
+    Env,
+    /// `environment:` block for a docker-compose service
+    Compose,
+    /// Custom template passed with --template
+    Template,
+}
+
+fn split_ini_list(list: &str) -> Vec<String> {
+    list.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Variables available to export templates.
+///
+/// `workshop_ids`, `mod_ids` and `maps` are the final ini lists (include/exclude applied),
+/// `items` holds one entry per workshop item in `workshop_ids` with `workshop_id`, `title`, `mod_ids`,
+/// `maps`, `last_updated` and `collections`. Its `mod_ids` and `maps` are filtered the same way.
+pub(crate) fn build_context(
+    mods_data: &[ModData],
+    workshop_items_string: &str,
+    mods_string: &str,
+    maps_string: Option<&str>,
+) -> TemplateContext {
+    let mut context = TemplateContext::new();
+
+    let workshop_ids = split_ini_list(workshop_items_string);
+    let mod_ids = split_ini_list(mods_string);
+    let maps = split_ini_list(maps_string.unwrap_or_default());
+    let kept = |names: &[String], final_list: &[String]| {
+        names
+            .iter()
+            .filter(|name| final_list.contains(name))
+            .cloned()
+            .collect::<Vec<String>>()
+    };
+
+    let items = mods_data
+        .iter()
+        .filter(|mod_data| workshop_ids.contains(&mod_data.mod_id.to_string()))
+        .map(|mod_data| {
+            let mut item = BTreeMap::new();
+            item.insert("workshop_id".to_owned(), TemplateValue::text(mod_data.mod_id));
+            item.insert("title".to_owned(), TemplateValue::text(&mod_data.title));
+            item.insert("mod_ids".to_owned(), TemplateValue::list(&kept(&mod_data.mod_name, &mod_ids)));
+            item.insert("maps".to_owned(), TemplateValue::list(&kept(&mod_data.map_name, &maps)));
+            item.insert("last_updated".to_owned(), TemplateValue::text(mod_data.last_updated));
+            item.insert("collections".to_owned(), TemplateValue::list(&mod_data.collections));
+            TemplateValue::Map(item)
+        })
+        .collect();
+
+    context.insert("workshop_ids".to_owned(), TemplateValue::list(&workshop_ids));
+    context.insert("mod_ids".to_owned(), TemplateValue::list(&mod_ids));
+    context.insert("maps".to_owned(), TemplateValue::list(&maps));
+    context.insert("items".to_owned(), TemplateValue::List(items));
+
+    context
+}
+
+const ENV_TEMPLATE: &str = "WORKSHOP_IDS={{ workshop_ids }}\nMOD_IDS={{ mod_ids }}\n";
+
+pub(crate) fn render_export(
+    format: ExportFormat,
+    context: &TemplateContext,
+    custom_template: Option<&str>,
+) -> Result<String, String> {
+    match format {
+        ExportFormat::Env => Ok(Template::parse(ENV_TEMPLATE)?.render(context)),
+        ExportFormat::Compose => {
+            let mut environment = BTreeMap::new();
+            for (variable, key) in [("WORKSHOP_IDS", "workshop_ids"), ("MOD_IDS", "mod_ids")] {
+                let value = context.get(key).map(|value| value.render(";")).unwrap_or_default();
+                environment.insert(variable, value);
+            }
+            let mut fragment = BTreeMap::new();
+            fragment.insert("environment", environment);
+            serde_yaml::to_string(&fragment).map_err(|e| e.to_string())
+        }
+        ExportFormat::Template => match custom_template {
+            Some(custom_template) => Ok(Template::parse(custom_template)?.render(context)),
+            None => Err("template export needs --template".to_owned()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(mod_id: u64, title: &str, mod_name: &[&str]) -> ModData {
+        ModData {
+            mod_id,
+            title: title.to_owned(),
+            mod_name: mod_name.iter().map(|name| name.to_string()).collect(),
+            last_updated: 1700000000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn render_export_test() {
+        let mods_data = [
+            item(1, "Hydrocraft", &["Hydrocraft", "HydroExtras"]),
+            item(2, "Brita's Weapon Pack", &["Brita"]),
+        ];
+        // item 2 is excluded, HydroExtras is excluded as a mod
+        let context = build_context(&mods_data, "1;5;", "Hydrocraft;LocalMod;", Some("Muldraugh, KY;"));
+
+        assert_eq!(
+            "WORKSHOP_IDS=1;5\nMOD_IDS=Hydrocraft;LocalMod\n",
+            render_export(ExportFormat::Env, &context, None).unwrap()
+        );
+        assert_eq!(
+            "environment:\n  MOD_IDS: Hydrocraft;LocalMod\n  WORKSHOP_IDS: 1;5\n",
+            render_export(ExportFormat::Compose, &context, None).unwrap()
+        );
+
+        let template = "{{#each items}}{{ workshop_id }} {{ title }}: {{ mod_ids | join \", \" }}\n{{/each}}maps={{ maps }}";
+        assert_eq!(
+            "1 Hydrocraft: Hydrocraft\nmaps=Muldraugh, KY",
+            render_export(ExportFormat::Template, &context, Some(template)).unwrap()
+        );
+        assert!(render_export(ExportFormat::Template, &context, None).is_err());
+    }
+}
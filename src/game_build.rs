@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+
+use log::error;
+
+use crate::config::{BuildSource, SteamCmdSettings};
+use crate::steamcmd::SteamCmd;
+use crate::vdf;
+
+pub const DEDICATED_SERVER_APP_ID: u64 = 380870;
+const DEFAULT_BRANCH: &str = "public";
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct InstalledBuild {
+    pub build_id: u64,
+    pub branch: String,
+}
+
+pub fn appmanifest_path(install_dir: &Path) -> PathBuf {
+    install_dir
+        .join("steamapps")
+        .join(format!("appmanifest_{DEDICATED_SERVER_APP_ID}.acf"))
+}
+
+pub fn parse_appmanifest(text: &str) -> Result<InstalledBuild, String> {
+    let root = vdf::parse(text)?;
+    let app_state = match root.get("AppState") {
+        Some(app_state) => app_state,
+        None => return Err("AppState section is missing".to_owned()),
+    };
+
+    let build_id = match app_state.get_u64("buildid") {
+        Some(build_id) => build_id,
+        None => return Err("buildid is missing".to_owned()),
+    };
+
+    // MountedConfig is what is installed, UserConfig what was asked for
+    let branch = ["MountedConfig", "UserConfig"]
+        .iter()
+        .filter_map(|section| app_state.get(section).and_then(|config| config.get_str("BetaKey")))
+        .find(|branch| !branch.is_empty())
+        .unwrap_or(DEFAULT_BRANCH)
+        .to_owned();
+
+    Ok(InstalledBuild { build_id, branch })
+}
+
+pub async fn load_installed_build(install_dir: &Path) -> Result<InstalledBuild, ()> {
+    let path = appmanifest_path(install_dir);
+    let manifest_data = match tokio::fs::read_to_string(&path).await {
+        Ok(manifest_data) => manifest_data,
+        Err(e) => {
+            error!("Failed to open {} - {}", path.display(), e);
+            return Err(());
+        }
+    };
+
+    match parse_appmanifest(&manifest_data) {
+        Ok(installed_build) => Ok(installed_build),
+        Err(e) => {
+            error!("Failed to parse {} - {}", path.display(), e);
+            Err(())
+        }
+    }
+}
+
+/// Cuts the `"380870" { ... }` block out of steamcmd's chatter around it.
+fn app_info_block(output: &str) -> Option<&str> {
+    let start = output.find(&format!("\"{DEDICATED_SERVER_APP_ID}\""))?;
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (pos, c) in output[start..].char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&output[start..=start + pos]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Build id of `branch` from `app_info_print` output.
+pub fn parse_app_info(output: &str, branch: &str) -> Result<u64, String> {
+    let block = match app_info_block(output) {
+        Some(block) => block,
+        None => return Err(format!("no app info for {DEDICATED_SERVER_APP_ID} in steamcmd output")),
+    };
+
+    let root = vdf::parse(block)?;
+    root.get(&DEDICATED_SERVER_APP_ID.to_string())
+        .and_then(|app| app.get("depots"))
+        .and_then(|depots| depots.get("branches"))
+        .and_then(|branches| branches.get(branch))
+        .and_then(|branch| branch.get_u64("buildid"))
+        .ok_or(format!("branch {branch} has no buildid"))
+}
+
+/// Latest build id of `branch`, `None` when checking is disabled.
+pub async fn latest_build(
+    source: &BuildSource,
+    steamcmd: Option<&SteamCmdSettings>,
+    branch: &str,
+) -> Result<Option<u64>, String> {
+    match source {
+        BuildSource::Disabled => Ok(None),
+        BuildSource::Stub { build_id } => Ok(Some(*build_id)),
+        BuildSource::SteamCmd => {
+            let steamcmd = match steamcmd {
+                Some(steamcmd) => steamcmd,
+                None => return Err("steamcmd is not configured - add a steamcmd section to the config".to_owned()),
+            };
+            let output = SteamCmd::new(steamcmd.clone())
+                .app_info(DEDICATED_SERVER_APP_ID)
+                .await?;
+            parse_app_info(&output, branch).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_parsing_test() {
+        let manifest = "\"AppState\"\n{\n\t\"appid\"\t\t\"380870\"\n\t\"buildid\"\t\t\"14000000\"\n\t\"UserConfig\"\n\t{\n\t\t\"BetaKey\"\t\t\"unstable\"\n\t}\n}\n";
+        assert_eq!(
+            InstalledBuild {
+                build_id: 14000000,
+                branch: "unstable".to_owned()
+            },
+            parse_appmanifest(manifest).unwrap()
+        );
+
+        let output = "Redirecting stderr to 'stderr.txt'\nAppID : 380870, change number : 1/0, last change : Mon Jan  1\n\"380870\"\n{\n\t\"common\" { \"name\" \"Project Zomboid Dedicated Server\" }\n\t\"depots\"\n\t{\n\t\t\"branches\"\n\t\t{\n\t\t\t\"public\" { \"buildid\" \"14000001\" }\n\t\t\t\"unstable\" { \"buildid\" \"14000002\" \"description\" \"b42 {unstable}\" }\n\t\t}\n\t}\n}\nUnloading Steam API...OK\n";
+        assert_eq!(Ok(14000001), parse_app_info(output, "public"));
+        assert_eq!(Ok(14000002), parse_app_info(output, "unstable"));
+        assert!(parse_app_info(output, "iwillbackupmysave").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use log::{info, warn};
+
+use crate::config::ZSOConfig;
+use crate::steam_api_client::ModData;
+use crate::zomboid_maps::VANILLA_MAP;
+use crate::zomboid_utils::IniModLists;
+
+/// Parses `WorkshopItems=` entries, skipping anything that isn't a workshop id.
+pub(crate) fn parse_workshop_ids(workshop_items: &[String]) -> Vec<u64> {
+    let mut workshop_ids: Vec<u64> = vec![];
+
+    for workshop_item in workshop_items {
+        match workshop_item.parse::<u64>() {
+            Ok(workshop_id) => {
+                if !workshop_ids.contains(&workshop_id) {
+                    workshop_ids.push(workshop_id)
+                }
+            }
+            Err(_) => warn!("Skipping invalid workshop item '{}'", workshop_item),
+        }
+    }
+
+    workshop_ids
+}
+
+/// Builds include/exclude lists so that generating strings from `base.collections`
+/// reproduces what the server ini has today.
+pub(crate) fn build_import_config(
+    mut base: ZSOConfig,
+    current: &IniModLists,
+    collection_items: &[u64],
+    mods_data: &[ModData],
+) -> ZSOConfig {
+    let ini_items = parse_workshop_ids(&current.workshop_items);
+    let include = &mut base.workshop_settings.include;
+    let exclude = &mut base.workshop_settings.exclude;
+
+    for workshop_id in &ini_items {
+        if !collection_items.contains(workshop_id) && !include.workshop_items.contains(workshop_id) {
+            include.workshop_items.push(*workshop_id);
+        }
+    }
+
+    for workshop_id in collection_items {
+        if !ini_items.contains(workshop_id) && !exclude.workshop_items.contains(workshop_id) {
+            exclude.workshop_items.push(*workshop_id);
+        }
+    }
+
+    let enabled_items = mods_data
+        .iter()
+        .filter(|mod_data| ini_items.contains(&mod_data.mod_id))
+        .collect::<Vec<&ModData>>();
+
+    let mut known_mods: Vec<&String> = vec![];
+    let mut known_maps: Vec<&String> = vec![];
+
+    for mod_data in &enabled_items {
+        for mod_name in &mod_data.mod_name {
+            known_mods.push(mod_name);
+            if !current.mods.contains(mod_name) && !exclude.mods.contains(mod_name) {
+                exclude.mods.push(mod_name.clone());
+            }
+        }
+        for map_name in &mod_data.map_name {
+            known_maps.push(map_name);
+            if !current.maps.contains(map_name) && !exclude.maps.contains(map_name) {
+                exclude.maps.push(map_name.clone());
+            }
+        }
+    }
+
+    // excluded collection items would still bring their mods and maps, generating only skips those by name
+    for mod_data in mods_data
+        .iter()
+        .filter(|mod_data| exclude.workshop_items.contains(&mod_data.mod_id))
+    {
+        for mod_name in &mod_data.mod_name {
+            if !current.mods.contains(mod_name) && !exclude.mods.contains(mod_name) {
+                exclude.mods.push(mod_name.clone());
+            }
+        }
+        for map_name in &mod_data.map_name {
+            if !current.maps.contains(map_name) && !exclude.maps.contains(map_name) {
+                exclude.maps.push(map_name.clone());
+            }
+        }
+    }
+
+    for mod_name in &current.mods {
+        if !known_mods.contains(&mod_name) && !include.mods.contains(mod_name) {
+            warn!("Mod {} is not provided by any workshop item, keeping it in include", mod_name);
+            include.mods.push(mod_name.clone());
+        }
+    }
+
+    for map_name in &current.maps {
+        if map_name != VANILLA_MAP && !known_maps.contains(&map_name) && !include.maps.contains(map_name) {
+            include.maps.push(map_name.clone());
+        }
+    }
+
+    info!(
+        "Imported include: {} items, {} mods, {} maps; exclude: {} items, {} mods, {} maps",
+        include.workshop_items.len(),
+        include.mods.len(),
+        include.maps.len(),
+        exclude.workshop_items.len(),
+        exclude.mods.len(),
+        exclude.maps.len()
+    );
+
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zomboid_utils::{generate_map_string, generate_mods_string, generate_workshop_items_string};
+
+    fn mod_data(mod_id: u64, mod_name: &[&str], map_name: &[&str]) -> ModData {
+        ModData {
+            mod_id,
+            mod_name: mod_name.iter().map(|name| name.to_string()).collect(),
+            map_name: map_name.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_workshop_ids_test() {
+        assert_eq!(vec![1, 2], parse_workshop_ids(&strings(&["1", "abc", "2", "1", ""])));
+    }
+
+    #[test]
+    fn build_import_config_test() {
+        let current = IniModLists {
+            workshop_items: strings(&["1", "2", "9"]),
+            mods: strings(&["Hydrocraft", "Bedford", "LocalMod"]),
+            maps: strings(&["Bedford Falls", "Custom Map", VANILLA_MAP]),
+        };
+        let mods_data = [
+            mod_data(1, &["Hydrocraft", "HydroExtras"], &[]),
+            mod_data(2, &["Bedford"], &["Bedford Falls", "Bedford Falls Extra"]),
+            mod_data(3, &["Arsenal"], &["Arsenal Range"]),
+        ];
+
+        let config = build_import_config(ZSOConfig::default(), &current, &[1, 2, 3], &mods_data);
+        let include = &config.workshop_settings.include;
+        let exclude = &config.workshop_settings.exclude;
+
+        assert_eq!(vec![9], include.workshop_items);
+        assert_eq!(strings(&["LocalMod"]), include.mods);
+        assert_eq!(strings(&["Custom Map"]), include.maps);
+        assert_eq!(vec![3], exclude.workshop_items);
+        assert_eq!(strings(&["HydroExtras", "Arsenal"]), exclude.mods);
+        assert_eq!(strings(&["Bedford Falls Extra", "Arsenal Range"]), exclude.maps);
+
+        // generating from the collections gives back what the ini has
+        let mods_data = mods_data.to_vec();
+        assert_eq!(
+            "1;2;9;",
+            generate_workshop_items_string(&mods_data, &include.workshop_items, &exclude.workshop_items)
+        );
+        assert_eq!("Hydrocraft;Bedford;LocalMod;", generate_mods_string(&mods_data, &include.mods, &exclude.mods));
+        assert_eq!(
+            "Bedford Falls;Custom Map;Muldraugh, KY;",
+            generate_map_string(&mods_data, &include.maps, &exclude.maps)
+        );
+
+        // a name the ini still uses stays, even when an excluded item declares it too
+        let shared = [mod_data(2, &["Bedford"], &[]), mod_data(3, &["Arsenal", "Bedford"], &[])];
+        let config = build_import_config(ZSOConfig::default(), &current, &[2, 3], &shared);
+        assert_eq!(strings(&["Arsenal"]), config.workshop_settings.exclude.mods);
+    }
+}
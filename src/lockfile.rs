@@ -0,0 +1,245 @@
+use std::path::{Path, PathBuf};
+
+use log::{error, info};
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::steam_api_client::ModData;
+
+pub const LOCK_FILE_NAME: &str = "zso.lock";
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedItem {
+    pub workshop_id: u64,
+    pub title: String,
+    pub time_updated: u64,
+    pub mod_ids: Vec<String>,
+    pub maps: Vec<String>,
+    pub collections: Vec<u64>,
+}
+
+impl From<&ModData> for LockedItem {
+    fn from(mod_data: &ModData) -> Self {
+        Self {
+            workshop_id: mod_data.mod_id,
+            title: mod_data.title.clone(),
+            time_updated: mod_data.last_updated,
+            mod_ids: mod_data.mod_name.clone(),
+            maps: mod_data.map_name.clone(),
+            collections: mod_data.collections.clone(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub items: Vec<LockedItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LockChange {
+    Added(LockedItem),
+    Removed(LockedItem),
+    Changed { locked: LockedItem, resolved: LockedItem },
+}
+
+/// `zso.lock` lives next to the config file.
+pub fn lock_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(LOCK_FILE_NAME)
+}
+
+impl Lockfile {
+    /// Locks the items that end up in WorkshopItems, sorted so the file diffs nicely in git.
+    pub fn from_mods_data(mods_data: &[ModData], excluded_items: &[u64]) -> Self {
+        let mut items = mods_data
+            .iter()
+            .filter(|mod_data| !excluded_items.contains(&mod_data.mod_id))
+            .map(LockedItem::from)
+            .collect::<Vec<LockedItem>>();
+        items.sort_by_key(|item| item.workshop_id);
+        items.dedup_by_key(|item| item.workshop_id);
+        Self { items }
+    }
+
+    pub async fn load(path: &Path) -> Result<Option<Self>, ()> {
+        let lock_data = match tokio::fs::read_to_string(path).await {
+            Ok(lock_data) => lock_data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                error!("Failed to open lock file - {}", e);
+                return Err(());
+            }
+        };
+
+        match serde_yaml::from_str(&lock_data) {
+            Ok(lockfile) => Ok(Some(lockfile)),
+            Err(e) => {
+                error!("Failed to parse lock file {} - {}", path.display(), e);
+                Err(())
+            }
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), ()> {
+        let lock_data = match serde_yaml::to_string(self) {
+            Ok(lock_data) => lock_data,
+            Err(e) => {
+                error!("Failed to serialize lock file - {}", e);
+                return Err(());
+            }
+        };
+
+        match tokio::fs::write(path, lock_data.as_bytes()).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to write lock file - {}", e);
+                Err(())
+            }
+        }
+    }
+
+    pub fn diff(&self, resolved: &Lockfile) -> Vec<LockChange> {
+        let mut changes: Vec<LockChange> = vec![];
+
+        for item in &resolved.items {
+            match self.items.iter().find(|locked| locked.workshop_id == item.workshop_id) {
+                Some(locked) if locked != item => changes.push(LockChange::Changed {
+                    locked: locked.clone(),
+                    resolved: item.clone(),
+                }),
+                Some(_) => {}
+                None => changes.push(LockChange::Added(item.clone())),
+            }
+        }
+
+        for locked in &self.items {
+            if !resolved.items.iter().any(|item| item.workshop_id == locked.workshop_id) {
+                changes.push(LockChange::Removed(locked.clone()));
+            }
+        }
+
+        changes
+    }
+}
+
+/// Logs the changes, they only go through with `--update`. Stdout is left to `--output`.
+pub fn accept_changes(lock_path: &Path, changes: &[LockChange], update: bool) -> Result<(), ()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    info!("{} changes to {}:", changes.len(), lock_path.display());
+    for change in changes {
+        info!("{}", change);
+    }
+
+    if !update {
+        error!("Workshop items changed since {} was written - re-run with --update to accept them", lock_path.display());
+        return Err(());
+    }
+    Ok(())
+}
+
+fn describe_lists(item: &LockedItem) -> String {
+    let mut description = format!("mods: {}", item.mod_ids.join(";"));
+    if !item.maps.is_empty() {
+        description += &format!(", maps: {}", item.maps.join(";"));
+    }
+    description
+}
+
+impl std::fmt::Display for LockChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockChange::Added(item) => write!(
+                f,
+                "+ {} {} ({}, updated {})",
+                item.workshop_id,
+                item.title,
+                describe_lists(item),
+                item.time_updated
+            ),
+            LockChange::Removed(item) => write!(f, "- {} {} ({})", item.workshop_id, item.title, describe_lists(item)),
+            LockChange::Changed { locked, resolved } => {
+                write!(f, "~ {} {}", resolved.workshop_id, resolved.title)?;
+                if locked.time_updated != resolved.time_updated {
+                    write!(f, "\n    updated: {} -> {}", locked.time_updated, resolved.time_updated)?;
+                }
+                if locked.mod_ids != resolved.mod_ids {
+                    write!(f, "\n    mods: {} -> {}", locked.mod_ids.join(";"), resolved.mod_ids.join(";"))?;
+                }
+                if locked.maps != resolved.maps {
+                    write!(f, "\n    maps: {} -> {}", locked.maps.join(";"), resolved.maps.join(";"))?;
+                }
+                if locked.collections != resolved.collections {
+                    write!(f, "\n    collections: {:?} -> {:?}", locked.collections, resolved.collections)?;
+                }
+                if locked.title != resolved.title {
+                    write!(f, "\n    title: {} -> {}", locked.title, resolved.title)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(workshop_id: u64, time_updated: u64, mod_ids: &[&str]) -> LockedItem {
+        LockedItem {
+            workshop_id,
+            title: format!("Item {workshop_id}"),
+            time_updated,
+            mod_ids: mod_ids.iter().map(|mod_id| mod_id.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn diff_test() {
+        let locked = Lockfile {
+            items: vec![item(1, 100, &["Hydrocraft"]), item(2, 100, &["Brita"]), item(3, 100, &["Arsenal"])],
+        };
+        let resolved = Lockfile {
+            items: vec![item(1, 100, &["Hydrocraft"]), item(2, 200, &["Brita", "BritaArmor"]), item(4, 100, &["Bedford"])],
+        };
+
+        let changes = locked.diff(&resolved);
+        assert_eq!(
+            vec![
+                LockChange::Changed {
+                    locked: item(2, 100, &["Brita"]),
+                    resolved: item(2, 200, &["Brita", "BritaArmor"]),
+                },
+                LockChange::Added(item(4, 100, &["Bedford"])),
+                LockChange::Removed(item(3, 100, &["Arsenal"])),
+            ],
+            changes
+        );
+        assert_eq!("~ 2 Item 2\n    updated: 100 -> 200\n    mods: Brita -> Brita;BritaArmor", changes[0].to_string());
+        assert!(resolved.diff(&resolved).is_empty());
+    }
+
+    #[test]
+    fn accept_changes_test() {
+        let lock_path = Path::new(LOCK_FILE_NAME);
+        let changes = vec![LockChange::Added(item(4, 100, &["Bedford"]))];
+
+        assert_eq!(Err(()), accept_changes(lock_path, &changes, false));
+        assert_eq!(Ok(()), accept_changes(lock_path, &changes, true));
+        assert_eq!(Ok(()), accept_changes(lock_path, &[], false));
+    }
+
+    #[test]
+    fn from_mods_data_test() {
+        let mods_data = [3, 1, 2, 1].map(|mod_id| ModData {
+            mod_id,
+            ..Default::default()
+        });
+
+        let lockfile = Lockfile::from_mods_data(&mods_data, &[2]);
+        assert_eq!(vec![1, 3], lockfile.items.iter().map(|item| item.workshop_id).collect::<Vec<u64>>());
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use log::warn;
+use regex::Regex;
+use serde_derive::Serialize;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+
+pub const CONSOLE_LOG_NAME: &str = "server-console.txt";
+
+static CONSOLE_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    // "LOG  : General     , 1729300000000> 12,345> message", the tick counter is optional
+    Regex::new(r"^(?P<level>LOG|WARN|ERROR|DEBUG)\s*:\s*(?P<subsystem>\w+)[^>]*>(?:\s*[\d,]+>)?\s*(?P<message>.*)$")
+        .unwrap()
+});
+static MOD_NOT_FOUND_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"mod "(?P<mod_id>[^"]+)" not found"#).unwrap());
+static OUT_OF_DATE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(mods need update|workshop item.*(out of date|needs update)|out of date workshop item)").unwrap()
+});
+static WORKSHOP_ID_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(?P<id>\d{7,})\b").unwrap());
+static EXCEPTION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:[\w$]+\.)+[\w$]*(?:Exception|Error)\b(?::.*)?$").unwrap());
+static USER_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\[[^\]]+\]\s+(?P<steam_id>\d+)\s+"(?P<name>[^"]*)"\s+(?P<action>fully connected|disconnected player)"#)
+        .unwrap()
+});
+static CHAT_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\[[^\]]+\](?:\[\w+\])?\s*Got message:ChatMessage\{chat=(?P<chat>[^,]*), author='(?P<author>[^']*)', text='(?P<text>.*)'\}")
+        .unwrap()
+});
+static ADMIN_LINE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[[^\]]+\]\s+(?P<admin>\S+)\s+(?P<command>.+?)\.?$").unwrap());
+
+/// Which log file a line came from, they all have their own format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSource {
+    Console,
+    Chat,
+    User,
+    Admin,
+}
+
+impl LogSource {
+    pub fn for_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        match file_name {
+            CONSOLE_LOG_NAME | "console.txt" => Some(LogSource::Console),
+            _ if file_name.ends_with("_chat.txt") => Some(LogSource::Chat),
+            _ if file_name.ends_with("_user.txt") => Some(LogSource::User),
+            _ if file_name.ends_with("_admin.txt") => Some(LogSource::Admin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    ServerStarted,
+    ModLoadError { mod_id: Option<String>, message: String },
+    WorkshopOutOfDate { workshop_id: Option<u64>, message: String },
+    PlayerConnected { steam_id: u64, name: String },
+    PlayerDisconnected { steam_id: u64, name: String },
+    Chat { chat: String, author: String, text: String },
+    AdminCommand { admin: String, command: String },
+    CrashStackTrace { exception: String, trace: Vec<String> },
+}
+
+/// Turns lines of one log file into events. Stack traces span several lines,
+/// so they come out once a line that isn't part of them shows up, or on `flush`.
+#[derive(Debug)]
+pub struct LineParser {
+    source: LogSource,
+    pending_trace: Option<(String, Vec<String>)>,
+}
+
+impl LineParser {
+    pub fn new(source: LogSource) -> Self {
+        Self {
+            source,
+            pending_trace: None,
+        }
+    }
+
+    pub fn flush(&mut self) -> Option<ServerEvent> {
+        self.pending_trace
+            .take()
+            .map(|(exception, trace)| ServerEvent::CrashStackTrace { exception, trace })
+    }
+
+    pub fn parse_line(&mut self, line: &str) -> Vec<ServerEvent> {
+        let line = line.trim_end_matches('\r');
+        let mut events = vec![];
+
+        match self.source {
+            LogSource::Console => {
+                if let Some((_, trace)) = &mut self.pending_trace {
+                    let trimmed = line.trim_start();
+                    if trimmed.starts_with("at ") || trimmed.starts_with("Caused by:") || trimmed.starts_with("...") {
+                        trace.push(trimmed.to_owned());
+                        return events;
+                    }
+                    events.extend(self.flush());
+                }
+                events.extend(self.parse_console_line(line));
+            }
+            LogSource::Chat => {
+                if let Some(captures) = CHAT_LINE_RE.captures(line) {
+                    events.push(ServerEvent::Chat {
+                        chat: captures["chat"].to_owned(),
+                        author: captures["author"].to_owned(),
+                        text: captures["text"].to_owned(),
+                    });
+                }
+            }
+            LogSource::User => {
+                if let Some(captures) = USER_LINE_RE.captures(line) {
+                    let steam_id = captures["steam_id"].parse().unwrap_or_default();
+                    let name = captures["name"].to_owned();
+                    events.push(match &captures["action"] {
+                        "fully connected" => ServerEvent::PlayerConnected { steam_id, name },
+                        _ => ServerEvent::PlayerDisconnected { steam_id, name },
+                    });
+                }
+            }
+            LogSource::Admin => {
+                if let Some(captures) = ADMIN_LINE_RE.captures(line) {
+                    events.push(ServerEvent::AdminCommand {
+                        admin: captures["admin"].to_owned(),
+                        command: captures["command"].to_owned(),
+                    });
+                }
+            }
+        }
+
+        events
+    }
+
+    fn parse_console_line(&mut self, line: &str) -> Option<ServerEvent> {
+        let (level, message) = match CONSOLE_LINE_RE.captures(line) {
+            Some(captures) => (
+                captures.name("level").map(|level| level.as_str()).unwrap_or_default(),
+                captures.name("message").map(|message| message.as_str()).unwrap_or_default(),
+            ),
+            None => ("", line.trim()),
+        };
+
+        if EXCEPTION_RE.is_match(message) {
+            self.pending_trace = Some((message.to_owned(), vec![]));
+            return None;
+        }
+
+        if message.contains("SERVER STARTED") {
+            return Some(ServerEvent::ServerStarted);
+        }
+
+        if OUT_OF_DATE_RE.is_match(message) {
+            return Some(ServerEvent::WorkshopOutOfDate {
+                workshop_id: WORKSHOP_ID_RE
+                    .captures(message)
+                    .and_then(|captures| captures["id"].parse().ok()),
+                message: message.to_owned(),
+            });
+        }
+
+        if let Some(captures) = MOD_NOT_FOUND_RE.captures(message) {
+            return Some(ServerEvent::ModLoadError {
+                mod_id: Some(captures["mod_id"].to_owned()),
+                message: message.to_owned(),
+            });
+        }
+
+        let lowercase = message.to_lowercase();
+        if matches!(level, "ERROR" | "WARN") && lowercase.contains("mod") && lowercase.contains("fail") {
+            return Some(ServerEvent::ModLoadError {
+                mod_id: None,
+                message: message.to_owned(),
+            });
+        }
+
+        None
+    }
+}
+
+struct TailedFile {
+    offset: u64,
+    partial: String,
+    parser: LineParser,
+}
+
+/// Follows `server-console.txt` and the chat, user and admin logs in `Logs/`.
+pub struct LogTailer {
+    console_log: PathBuf,
+    logs_dir: PathBuf,
+    files: BTreeMap<PathBuf, TailedFile>,
+    from_start: bool,
+}
+
+impl LogTailer {
+    /// Files that already exist are followed from their end unless `from_start` is set.
+    pub fn new(zomboid_dir: &Path, from_start: bool) -> Self {
+        Self {
+            console_log: zomboid_dir.join(CONSOLE_LOG_NAME),
+            logs_dir: zomboid_dir.join("Logs"),
+            files: BTreeMap::new(),
+            from_start,
+        }
+    }
+
+    async fn discover(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.console_log.clone()];
+
+        if let Ok(mut entries) = tokio::fs::read_dir(&self.logs_dir).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                if LogSource::for_path(&path).is_some() {
+                    paths.push(path);
+                }
+            }
+        }
+
+        paths
+    }
+
+    async fn read_new(path: &Path, tailed: &mut TailedFile) -> std::io::Result<String> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+
+        // the server recreates server-console.txt on every start
+        if len < tailed.offset {
+            tailed.offset = 0;
+            tailed.partial.clear();
+        }
+
+        file.seek(std::io::SeekFrom::Start(tailed.offset)).await?;
+        let mut buffer = vec![];
+        file.read_to_end(&mut buffer).await?;
+        tailed.offset += buffer.len() as u64;
+
+        Ok(String::from_utf8_lossy(&buffer).to_string())
+    }
+
+    pub async fn poll(&mut self) -> Vec<ServerEvent> {
+        let mut events = vec![];
+        let first_poll = self.files.is_empty();
+
+        for path in self.discover().await {
+            let source = match LogSource::for_path(&path) {
+                Some(source) => source,
+                None => continue,
+            };
+
+            if !self.files.contains_key(&path) {
+                let offset = match first_poll && !self.from_start {
+                    true => tokio::fs::metadata(&path).await.map(|metadata| metadata.len()).unwrap_or_default(),
+                    false => 0,
+                };
+                self.files.insert(
+                    path.clone(),
+                    TailedFile {
+                        offset,
+                        partial: String::new(),
+                        parser: LineParser::new(source),
+                    },
+                );
+            }
+            let tailed = self.files.get_mut(&path).unwrap();
+
+            let text = match Self::read_new(&path, tailed).await {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    warn!("Failed to read {} - {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            if text.is_empty() {
+                events.extend(tailed.parser.flush());
+                continue;
+            }
+
+            tailed.partial += &text;
+            let complete = match tailed.partial.rfind('\n') {
+                Some(pos) => tailed.partial.drain(..=pos).collect::<String>(),
+                None => continue,
+            };
+            for line in complete.lines() {
+                events.extend(tailed.parser.parse_line(line));
+            }
+        }
+
+        events
+    }
+
+    /// Polls in the background and sends every event down the returned channel.
+    pub fn spawn(mut self, interval: Duration) -> mpsc::UnboundedReceiver<ServerEvent> {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                for event in self.poll().await {
+                    if event_tx.send(event).is_err() {
+                        return;
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+
+        event_rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONSOLE_LOG: &str = r#"LOG  : General     , 1729300000000> 0> versionNumber=41.78.16 demo=false
+WARN : Mod         , 1729300000100> 0> ZomboidFileSystem.loadModAndRequired> required mod "tsarslib" not found
+ERROR: General     , 1729300000200> 0> ZomboidFileSystem.loadMods> mod "BrokenMod" not found
+LOG  : General     , 1729300000300> 0> *** SERVER STARTED ****
+LOG  : General     , 1729300000400> 0> CheckModsNeedUpdate: Mods need update, workshop item 2392709985
+ERROR: General     , 1729300000500> 0> java.lang.NullPointerException: Cannot invoke "zombie.iso.IsoGridSquare.getX()"
+	at zombie.network.GameServer.main(GameServer.java:1000)
+	at zombie.network.GameServer.run(GameServer.java:42)
+Caused by: java.lang.IllegalStateException
+	... 2 more
+LOG  : General     , 1729300000600> 0> Saving players
+"#;
+
+    const USER_LOG: &str = r#"[19-10-26 03:14:00.123] 76561198000000001 "Kate" attempting to join.
+[19-10-26 03:14:05.456] 76561198000000001 "Kate" fully connected (10613,9348,0).
+[19-10-26 03:44:05.456] 76561198000000001 "Kate" disconnected player (10615,9350,0).
+"#;
+
+    const CHAT_LOG: &str = r#"[19-10-26 03:15:00.000][info] Got message:ChatMessage{chat=General, author='Kate', text='!postpone please'}.
+"#;
+
+    const ADMIN_LOG: &str = r#"[19-10-26 03:16:00.000] admin teleported Kate to 10000,10000,0.
+"#;
+
+    fn parse_all(source: LogSource, text: &str) -> Vec<ServerEvent> {
+        let mut parser = LineParser::new(source);
+        let mut events = text
+            .lines()
+            .flat_map(|line| parser.parse_line(line))
+            .collect::<Vec<ServerEvent>>();
+        events.extend(parser.flush());
+        events
+    }
+
+    #[test]
+    fn console_log_test() {
+        let events = parse_all(LogSource::Console, CONSOLE_LOG);
+
+        assert_eq!(
+            vec![
+                ServerEvent::ModLoadError {
+                    mod_id: Some("tsarslib".to_owned()),
+                    message: "ZomboidFileSystem.loadModAndRequired> required mod \"tsarslib\" not found".to_owned(),
+                },
+                ServerEvent::ModLoadError {
+                    mod_id: Some("BrokenMod".to_owned()),
+                    message: "ZomboidFileSystem.loadMods> mod \"BrokenMod\" not found".to_owned(),
+                },
+                ServerEvent::ServerStarted,
+                ServerEvent::WorkshopOutOfDate {
+                    workshop_id: Some(2392709985),
+                    message: "CheckModsNeedUpdate: Mods need update, workshop item 2392709985".to_owned(),
+                },
+                ServerEvent::CrashStackTrace {
+                    exception: "java.lang.NullPointerException: Cannot invoke \"zombie.iso.IsoGridSquare.getX()\""
+                        .to_owned(),
+                    trace: vec![
+                        "at zombie.network.GameServer.main(GameServer.java:1000)".to_owned(),
+                        "at zombie.network.GameServer.run(GameServer.java:42)".to_owned(),
+                        "Caused by: java.lang.IllegalStateException".to_owned(),
+                        "... 2 more".to_owned(),
+                    ],
+                },
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn player_logs_test() {
+        assert_eq!(
+            vec![
+                ServerEvent::PlayerConnected {
+                    steam_id: 76561198000000001,
+                    name: "Kate".to_owned()
+                },
+                ServerEvent::PlayerDisconnected {
+                    steam_id: 76561198000000001,
+                    name: "Kate".to_owned()
+                },
+            ],
+            parse_all(LogSource::User, USER_LOG)
+        );
+        assert_eq!(
+            vec![ServerEvent::Chat {
+                chat: "General".to_owned(),
+                author: "Kate".to_owned(),
+                text: "!postpone please".to_owned()
+            }],
+            parse_all(LogSource::Chat, CHAT_LOG)
+        );
+        assert_eq!(
+            vec![ServerEvent::AdminCommand {
+                admin: "admin".to_owned(),
+                command: "teleported Kate to 10000,10000,0".to_owned()
+            }],
+            parse_all(LogSource::Admin, ADMIN_LOG)
+        );
+    }
+
+    #[tokio::test]
+    async fn log_tailer_test() {
+        let test_dir = std::env::temp_dir().join(format!("zso-tail-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(test_dir.join("Logs")).unwrap();
+        std::fs::write(test_dir.join(CONSOLE_LOG_NAME), "LOG  : General     , 1> 0> *** SERVER STARTED ****\n").unwrap();
+
+        // existing lines are history, only what gets written afterwards is reported
+        let mut tailer = LogTailer::new(&test_dir, false);
+        let before = tailer.poll().await;
+
+        std::fs::write(test_dir.join("Logs/19-10-26_03-14_user.txt"), USER_LOG).unwrap();
+        // restarted server, shorter console log
+        std::fs::write(test_dir.join(CONSOLE_LOG_NAME), "LOG  : General, 2> *** SERVER STARTED ****\n").unwrap();
+        let after = tailer.poll().await;
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert!(before.is_empty());
+        assert_eq!(3, after.len());
+        assert_eq!(ServerEvent::ServerStarted, after[0]);
+    }
+}
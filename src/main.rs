@@ -1,13 +1,39 @@
 #![feature(lazy_cell)]
 #![feature(fs_try_exists)]
 
+mod backup;
+mod boot_check;
 mod config;
+mod export;
+mod game_build;
+mod import;
+mod lockfile;
+mod log_parser;
+mod maintenance;
+mod mod_conflicts;
+mod notify;
+mod operator;
 mod rcon;
+mod reboot_messages;
+mod report;
+mod resolve;
+mod run_history;
+mod scheduler;
+mod sandbox_vars;
+mod server_ini;
+mod spawn_regions;
+mod staging;
 mod steam_api_client;
 mod steam_api_client_schemes;
+mod steamcmd;
+mod supervisor;
+mod template;
+mod vdf;
+mod workshop_state;
+mod zomboid_maps;
 mod zomboid_utils;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
 use env_logger::Builder;
@@ -15,10 +41,10 @@ use env_logger::Target;
 use log::info;
 
 use log::LevelFilter;
-use log::{debug, error};
+use log::{debug, error, warn};
 use steam_api_client::SteamApiClient;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::sync::LazyLock;
 
 use crate::config::ZSOConfig;
@@ -36,10 +62,151 @@ struct Args {
 
     #[arg(short, long)]
     maps: bool,
+
+    /// Accept workshop changes that zso.lock doesn't cover and rewrite the lock
+    #[arg(long)]
+    update: bool,
+
+    /// Restart the server after changing the ini (--ini) and roll the change back if it doesn't start
+    #[arg(long)]
+    restart: bool,
+
+    /// Print the full resolved model instead of the ini lines
+    #[arg(long, value_enum)]
+    output: Option<resolve::OutputFormat>,
+
+    /// Render the mod set for docker images that take mods from environment variables
+    #[arg(long, value_enum)]
+    export: Option<export::ExportFormat>,
+
+    /// Template file for --export template
+    #[arg(long)]
+    template: Option<PathBuf>,
+
+    /// Write the export to a file instead of stdout
+    #[arg(long)]
+    export_file: Option<PathBuf>,
+
+    /// Render a mod list for players as Markdown or HTML
+    #[arg(long, value_enum)]
+    report: Option<report::ReportFormat>,
+
+    /// Write the report to a file instead of stdout
+    #[arg(long)]
+    report_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Read, change and diff the server SandboxVars.lua
+    Sandbox {
+        #[command(subcommand)]
+        action: SandboxAction,
+    },
+    /// Check the server ini (--ini) for bad values, port clashes and RCON mismatches
+    Lint,
+    /// Write a config from the WorkshopItems, Mods and Map lists of the server ini (--ini)
+    Import {
+        /// Collections providing the mods, added to the ones from an existing config
+        #[arg(long)]
+        collection: Vec<u64>,
+        /// Where to write the config, defaults to --config
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Download every resolved workshop item with steamcmd and check the content folders
+    Download {
+        /// Ask steamcmd to validate already downloaded items
+        #[arg(long)]
+        validate: bool,
+    },
+    /// List mods whose downloaded copy is older than the workshop version, exits with 2 if any are
+    Stale,
+    /// Poll for workshop and dedicated server updates and reboot the server when there are any
+    Watch {
+        /// Check once and exit instead of polling
+        #[arg(long)]
+        once: bool,
+    },
+    /// Run the server, restart it when it crashes and stop it gracefully on SIGTERM
+    Supervise,
+    /// Follow the server logs and print what happens as JSON lines
+    Events {
+        /// Also report what is already in the logs
+        #[arg(long)]
+        from_start: bool,
+    },
+    /// Run or inspect the scheduled tasks
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Back up, list, verify and restore the world
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+    /// Report collection changes since the previous run
+    Changes {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SandboxAction {
+    /// Print a value, or every value when no key is given
+    Get { key: Option<String> },
+    /// Set a single value and write the file
+    Set { key: String, value: String },
+    /// Show configured values that differ from the file
+    Diff,
+    /// Write the configured preset and values into the file
+    Apply,
+}
+
+#[derive(Subcommand, Debug)]
+enum ScheduleAction {
+    /// Show the next fire times of every task
+    List {
+        /// How many fire times to show per task
+        #[arg(long, default_value_t = 3)]
+        count: usize,
+    },
+    /// Run the tasks on their schedule
+    Run,
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupAction {
+    /// Save the world over RCON and archive it
+    Create {
+        /// Recorded in the manifest
+        #[arg(long, default_value = "manual")]
+        reason: String,
+    },
+    /// Show the backups in backup.dir, newest first
+    List,
+    /// Replace the world with a backup, after taking a safety copy of the current one
+    Restore {
+        archive: PathBuf,
+        /// Restore even though the server answers RCON, or RCON is not configured
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check an archive against its manifest, or every backup when none is given
+    Verify { archive: Option<PathBuf> },
 }
 
 static ZSO_CONFIG: LazyLock<ZSOConfig> = LazyLock::new(|| {
-    println!("initializing");
+    debug!("initializing");
     let args = Args::parse();
 
     match std::fs::try_exists(&args.config) {
@@ -71,13 +238,63 @@ async fn main() {
     builder.filter_module("tracing", LevelFilter::Warn);
     builder.filter_module("hyper", LevelFilter::Info);
     builder.filter_module("rustls", LevelFilter::Info);
-    builder.target(Target::Stdout);
+    builder.target(Target::Stderr);
     builder.init();
 
     let args = Args::parse();
 
+    match &args.command {
+        Some(Commands::Sandbox { action }) => {
+            sandbox_command(&args, action).await;
+            return;
+        }
+        Some(Commands::Lint) => {
+            lint_command(&args).await;
+            return;
+        }
+        Some(Commands::Download { validate }) => {
+            download_command(*validate).await;
+            return;
+        }
+        Some(Commands::Stale) => {
+            stale_command().await;
+            return;
+        }
+        Some(Commands::Watch { once }) => {
+            watch_command(*once).await;
+            return;
+        }
+        Some(Commands::Supervise) => {
+            supervise_command().await;
+            return;
+        }
+        Some(Commands::Events { from_start }) => {
+            events_command(*from_start).await;
+            return;
+        }
+        Some(Commands::Schedule { action }) => {
+            schedule_command(&args, action).await;
+            return;
+        }
+        Some(Commands::Backup { action }) => {
+            backup_command(action).await;
+            return;
+        }
+        Some(Commands::Changes { json }) => {
+            changes_command(&args, *json).await;
+            return;
+        }
+        Some(Commands::Import { collection, out, force }) => {
+            import_command(&args, collection, out.as_ref().unwrap_or(&args.config), *force).await;
+            return;
+        }
+        None => {}
+    }
+
     match &args.ini {
-        Some(args_ini_path) => zomboid_utils::ini_initial_check(args_ini_path),
+        Some(args_ini_path) => {
+            zomboid_utils::ini_initial_check(args_ini_path);
+        }
         None => {}
     }
 
@@ -93,20 +310,26 @@ async fn main() {
     let steam_api_client = SteamApiClient::new();
     debug!("Steam client is initialized");
 
-    let full_mod_list = steam_api_client
-        .get_list_of_mods_in_collections(ZSO_CONFIG.collections.clone())
-        .await;
-
-    info!("Total mods in collections: {}", &full_mod_list.len());
+    let (collections_children, mods_data) =
+        resolve::fetch_mods_data(&steam_api_client, &ZSO_CONFIG.collections).await;
 
-    if full_mod_list.len() == 0 {
+    if mods_data.is_empty() {
         error!("No mods to parse - aborting!");
         exit(0);
     }
 
-    let mods_data = steam_api_client.resolve_mods_data(full_mod_list).await;
-
-    info!("Total parsed mods: {}", &mods_data.len());
+    let previous_run = run_history::load_snapshot(&run_history::snapshot_path(&args.config)).await;
+    let current_run = run_history::RunSnapshot::new(&collections_children, &mods_data);
+    if let Some(previous_run) = &previous_run {
+        let change_report = run_history::ChangeReport::between(previous_run, &current_run);
+        for line in change_report.summary().lines() {
+            info!("{}", line);
+        }
+    }
+    let conflicts = mod_conflicts::detect_conflicts(&mods_data, &ZSO_CONFIG.workshop_settings);
+    mod_conflicts::report_conflicts(&conflicts, &mods_data);
+    let all_mods_data = mods_data.clone();
+    let mods_data = mod_conflicts::apply_resolutions(mods_data, &conflicts);
 
     let workshop_items_string = zomboid_utils::generate_workshop_items_string(
         &mods_data,
@@ -123,6 +346,24 @@ async fn main() {
     let mut maps_string = String::new();
 
     if args.maps {
+        match &ZSO_CONFIG.workshop_settings.content_dir {
+            Some(content_dir) => {
+                let install_dir = ZSO_CONFIG
+                    .server_settings
+                    .as_ref()
+                    .and_then(|server_settings| server_settings.install_dir.as_deref());
+                zomboid_maps::check_map_overlaps(
+                    &mods_data,
+                    content_dir,
+                    install_dir,
+                    &ZSO_CONFIG.workshop_settings.exclude.maps,
+                );
+            }
+            None => {
+                info!("workshop_settings.content_dir is not set - skipping map cell overlap check");
+            }
+        }
+
         maps_string = zomboid_utils::generate_map_string(
             &mods_data,
             &ZSO_CONFIG.workshop_settings.include.maps,
@@ -132,6 +373,72 @@ async fn main() {
 
     match &args.ini {
         Some(ini_path) => {
+            if mod_conflicts::blocks_ini_update(&conflicts, &ZSO_CONFIG.workshop_settings) {
+                let unresolved_conflicts = mod_conflicts::unresolved_count(&conflicts);
+                error!(
+                    "{} unresolved Mod ID/map folder conflicts - refusing to update server ini",
+                    unresolved_conflicts
+                );
+                exit(1)
+            }
+
+            let option_issues = server_ini::lint_options(&ZSO_CONFIG.server_options);
+            if option_issues.iter().any(|issue| issue.severity == server_ini::Severity::Error) {
+                server_ini::report_issues(&option_issues);
+                error!("server_options in the config have invalid values - refusing to update server ini");
+                exit(1)
+            }
+
+            let lock_path = lockfile::lock_path(&args.config);
+            let resolved_lock = lockfile::Lockfile::from_mods_data(
+                &mods_data,
+                &ZSO_CONFIG.workshop_settings.exclude.workshop_items,
+            );
+            let locked = match lockfile::Lockfile::load(&lock_path).await {
+                Ok(locked) => locked.unwrap_or_default(),
+                Err(_) => exit(1),
+            };
+            let lock_changes = locked.diff(&resolved_lock);
+
+            if lockfile::accept_changes(&lock_path, &lock_changes, args.update).is_err() {
+                exit(1)
+            }
+
+            if let Some(staging_settings) = &ZSO_CONFIG.staging {
+                let mod_set = staging::ModSet {
+                    workshop_items: &workshop_items_string,
+                    mods: &mods_string,
+                    maps: args.maps.then_some(maps_string.as_str()),
+                    mods_data: &mods_data,
+                };
+                if staging::preflight(&ZSO_CONFIG, staging_settings, ini_path, &mod_set).await.is_err() {
+                    error!("The new mod set didn't boot on the staging server - leaving the server ini alone");
+                    exit(1)
+                }
+            }
+
+            let restart_settings = match (args.restart, &ZSO_CONFIG.server_settings) {
+                (false, _) => None,
+                (true, Some(server_settings)) => Some(server_settings),
+                (true, None) => {
+                    error!("--restart needs server_settings in the config");
+                    exit(1)
+                }
+            };
+            let mut changed_files = vec![ini_path.clone(), lock_path.clone()];
+            if args.maps {
+                changed_files.push(spawn_regions::spawnregions_path(ini_path));
+            }
+            let snapshot = match boot_check::ConfigSnapshot::take(&changed_files).await {
+                Ok(snapshot) => snapshot,
+                Err(_) => exit(1),
+            };
+
+            if !lock_changes.is_empty() && backup::backup_if_configured(&ZSO_CONFIG, "mod change").await.is_err() {
+                error!("Backup failed - refusing to change the mod set");
+                exit(1)
+            }
+
             info!("Updating server ini");
             if !args.maps {
 
@@ -157,25 +464,600 @@ async fn main() {
                     Some(&maps_string),
                 ).await {
                     Ok(_) => {
-                        info!("Server config was updated. Exiting.");
+                        info!("Server config was updated.");
                     },
                     Err(_) => {
                         error!("Failed to update server config");
                         exit(1)
                     },
                 }
+
+                match &ZSO_CONFIG.workshop_settings.content_dir {
+                    Some(content_dir) => {
+                        match spawn_regions::update_spawnregions(
+                            &spawn_regions::spawnregions_path(ini_path),
+                            &mods_data,
+                            content_dir,
+                            &ZSO_CONFIG.workshop_settings.exclude.maps,
+                        ).await {
+                            Ok(_) => {
+                                info!("Spawn regions were updated. Exiting.");
+                            },
+                            Err(_) => {
+                                error!("Failed to update spawn regions");
+                                exit(1)
+                            },
+                        }
+                    }
+                    None => {
+                        warn!("workshop_settings.content_dir is not set - spawn regions were not updated");
+                    }
+                }
+            }
+
+            match server_ini::update_server_options(ini_path, &ZSO_CONFIG.server_options).await {
+                Ok(_) => {
+                    info!("Server options were updated.");
+                },
+                Err(_) => {
+                    error!("Failed to update server options");
+                    exit(1)
+                },
+            }
+
+            if !lock_changes.is_empty() {
+                match resolved_lock.save(&lock_path).await {
+                    Ok(_) => info!("{} was updated", lock_path.display()),
+                    Err(_) => exit(1),
+                }
+            }
+
+            if let Some(server_settings) = restart_settings {
+                match snapshot.changed().await {
+                    true => {
+                        let restarted = boot_check::restart_or_roll_back(&ZSO_CONFIG, server_settings, &snapshot).await;
+                        notify::flush().await;
+                        if restarted.is_err() {
+                            exit(1)
+                        }
+                    }
+                    false => info!("Nothing changed - not restarting the server"),
+                }
             }
         }
         None => {
-            info!("Generated strings for server config:\n");
-            println!("WorkshopItems={}", workshop_items_string);
-            println!("Mods={}", mods_string);
+            if args.output.is_none() && args.export.is_none() && args.report.is_none() {
+                info!("Generated strings for server config:\n");
+                println!("WorkshopItems={}", workshop_items_string);
+                println!("Mods={}", mods_string);
 
-            if args.maps {
-                println!("Map={}", maps_string);
+                if args.maps {
+                    println!("Map={}", maps_string);
+                }
             }
         }
     }
+
+    // only once the changes went through, a refused apply reports them again next time
+    if current_run.save(&run_history::snapshot_path(&args.config)).await.is_err() {
+        warn!("The next run will compare collections against an older snapshot");
+    }
+
+    if let Some(export_format) = args.export {
+        let custom_template = match &args.template {
+            Some(template_path) => match tokio::fs::read_to_string(template_path).await {
+                Ok(custom_template) => Some(custom_template),
+                Err(e) => {
+                    error!("Failed to open template {} - {}", template_path.display(), e);
+                    exit(1)
+                }
+            },
+            None => None,
+        };
+
+        let context = export::build_context(
+            &mods_data,
+            &workshop_items_string,
+            &mods_string,
+            args.maps.then_some(maps_string.as_str()),
+        );
+
+        let rendered = match export::render_export(export_format, &context, custom_template.as_deref()) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                error!("Failed to render export - {}", e);
+                exit(1)
+            }
+        };
+
+        match &args.export_file {
+            Some(export_file) => match tokio::fs::write(export_file, rendered.as_bytes()).await {
+                Ok(_) => info!("Export was written to {}", export_file.display()),
+                Err(e) => {
+                    error!("Failed to write {} - {}", export_file.display(), e);
+                    exit(1)
+                }
+            },
+            None => print!("{}", rendered),
+        }
+    }
+
+    if let Some(report_format) = args.report {
+        let report_items = mods_data
+            .iter()
+            .filter(|mod_data| !ZSO_CONFIG.workshop_settings.exclude.workshop_items.contains(&mod_data.mod_id))
+            .cloned()
+            .collect::<Vec<steam_api_client::ModData>>();
+        let rendered = report::render_report(report_format, &ZSO_CONFIG.collections, &report_items);
+
+        match &args.report_file {
+            Some(report_file) => match tokio::fs::write(report_file, rendered.as_bytes()).await {
+                Ok(_) => info!("Report was written to {}", report_file.display()),
+                Err(e) => {
+                    error!("Failed to write {} - {}", report_file.display(), e);
+                    exit(1)
+                }
+            },
+            None => print!("{}", rendered),
+        }
+    }
+
+    if let Some(output) = args.output {
+        let model = resolve::build_model(
+            &ZSO_CONFIG.collections,
+            &all_mods_data,
+            &ZSO_CONFIG.workshop_settings,
+            &conflicts,
+            (&workshop_items_string, &mods_string, args.maps.then_some(maps_string.as_str())),
+        );
+
+        match resolve::render_model(&model, output) {
+            Ok(rendered) => print!("{}", rendered),
+            Err(e) => {
+                error!("Failed to render resolve output - {}", e);
+                exit(1)
+            }
+        }
+    }
+}
+
+async fn download_command(validate: bool) {
+    let steamcmd_settings = match &ZSO_CONFIG.steamcmd {
+        Some(steamcmd_settings) => steamcmd_settings.clone(),
+        None => {
+            error!("steamcmd is not configured - add a steamcmd section to the config");
+            exit(1)
+        }
+    };
+
+    let steam_api_client = SteamApiClient::new();
+    let (_, mods_data) = resolve::fetch_mods_data(&steam_api_client, &ZSO_CONFIG.collections).await;
+    let conflicts = mod_conflicts::detect_conflicts(&mods_data, &ZSO_CONFIG.workshop_settings);
+    let mods_data = mod_conflicts::apply_resolutions(mods_data, &conflicts);
+
+    let mut workshop_ids: Vec<u64> = vec![];
+    for workshop_id in mods_data
+        .iter()
+        .map(|mod_data| mod_data.mod_id)
+        .chain(ZSO_CONFIG.workshop_settings.include.workshop_items.iter().copied())
+    {
+        if !workshop_ids.contains(&workshop_id)
+            && !ZSO_CONFIG.workshop_settings.exclude.workshop_items.contains(&workshop_id)
+        {
+            workshop_ids.push(workshop_id);
+        }
+    }
+
+    info!("Downloading {} workshop items with steamcmd", workshop_ids.len());
+
+    let downloads = steamcmd::SteamCmd::new(steamcmd_settings)
+        .download_items(&workshop_ids, validate)
+        .await;
+    let failed = downloads
+        .iter()
+        .filter(|download| matches!(download.result, steamcmd::DownloadResult::Failed { .. }))
+        .count();
+
+    if failed > 0 {
+        error!("{} of {} workshop items failed to download", failed, downloads.len());
+        exit(1)
+    }
+}
+
+async fn stale_command() {
+    let stale_items = match operator::find_stale_mods(&ZSO_CONFIG).await {
+        Ok(stale_items) => stale_items,
+        Err(_) => exit(1),
+    };
+    if stale_items.is_empty() {
+        info!("All workshop items on disk are up to date");
+        return;
+    }
+
+    for stale_item in &stale_items {
+        println!("{} {} - {}", stale_item.workshop_id, stale_item.title, stale_item.reason);
+    }
+    warn!("{} workshop items are stale on disk, a restart is needed", stale_items.len());
+    exit(2)
+}
+
+async fn watch_command(once: bool) {
+    let server_settings = match &ZSO_CONFIG.server_settings {
+        Some(server_settings) => server_settings,
+        None => {
+            error!("server_settings are not configured - watch needs a reboot_command");
+            exit(1)
+        }
+    };
+
+    if let Some(maintenance) = &server_settings.maintenance {
+        if let Err(e) = maintenance::validate(maintenance) {
+            error!("Invalid server_settings.maintenance - {}", e);
+            exit(1)
+        }
+    }
+
+    operator::watch(&ZSO_CONFIG, server_settings, once).await;
+    notify::flush().await;
+}
+
+async fn supervise_command() {
+    let supervisor_settings = match &ZSO_CONFIG.supervisor {
+        Some(supervisor_settings) => supervisor_settings.clone(),
+        None => {
+            error!("supervisor is not configured - add a supervisor section to the config");
+            exit(1)
+        }
+    };
+    let install_dir = ZSO_CONFIG
+        .server_settings
+        .as_ref()
+        .and_then(|server_settings| server_settings.install_dir.as_deref());
+
+    let supervisor = match supervisor::Supervisor::new(supervisor_settings, install_dir, &ZSO_CONFIG) {
+        Ok(supervisor) => supervisor,
+        Err(e) => {
+            error!("{}", e);
+            exit(1)
+        }
+    };
+
+    let supervised = supervisor.run().await;
+    notify::flush().await;
+    if supervised.is_err() {
+        exit(1)
+    }
+}
+
+async fn events_command(from_start: bool) {
+    let zomboid_dir = match ZSO_CONFIG
+        .server_settings
+        .as_ref()
+        .and_then(|server_settings| server_settings.zomboid_dir.as_ref())
+    {
+        Some(zomboid_dir) => zomboid_dir,
+        None => {
+            error!("server_settings.zomboid_dir is not set - can't find the server logs");
+            exit(1)
+        }
+    };
+
+    let mut events = log_parser::LogTailer::new(zomboid_dir, from_start).spawn(std::time::Duration::from_secs(1));
+    while let Some(event) = events.recv().await {
+        match serde_json::to_string(&event) {
+            Ok(event_json) => println!("{}", event_json),
+            Err(e) => error!("Failed to serialize event - {}", e),
+        }
+    }
+}
+
+async fn schedule_command(args: &Args, action: &ScheduleAction) {
+    let schedule_settings = match &ZSO_CONFIG.schedule {
+        Some(schedule_settings) => schedule_settings,
+        None => {
+            error!("schedule is not configured - add a schedule section to the config");
+            exit(1)
+        }
+    };
+
+    match action {
+        ScheduleAction::List { count } => {
+            if let Err(e) = scheduler::list(schedule_settings, *count) {
+                error!("{}", e);
+                exit(1)
+            }
+        }
+        ScheduleAction::Run => {
+            let scheduler = scheduler::Scheduler::new(&ZSO_CONFIG, &args.config).await;
+            if scheduler.run(schedule_settings).await.is_err() {
+                exit(1)
+            }
+        }
+    }
+}
+
+async fn backup_command(action: &BackupAction) {
+    let backup_settings = match &ZSO_CONFIG.backup {
+        Some(backup_settings) => backup_settings,
+        None => {
+            error!("backup is not configured - add a backup section to the config");
+            exit(1)
+        }
+    };
+
+    match action {
+        BackupAction::Create { reason } => match backup::create_backup(&ZSO_CONFIG, reason).await {
+            Ok(archive_path) => println!("{}", archive_path.display()),
+            Err(_) => exit(1),
+        },
+        BackupAction::List => {
+            for backup in backup::list_backups(backup_settings) {
+                let created = chrono::DateTime::from_timestamp(backup.manifest.created, 0).unwrap_or_default();
+                println!(
+                    "{}  {}  {} files, {} bytes  {}",
+                    created.format("%Y-%m-%d %H:%M:%S"),
+                    backup.path.display(),
+                    backup.manifest.files.len(),
+                    backup.archive_size,
+                    backup.manifest.reason
+                );
+            }
+        }
+        BackupAction::Restore { archive, force } => {
+            if backup::restore_backup(&ZSO_CONFIG, archive, *force).await.is_err() {
+                exit(1)
+            }
+        }
+        BackupAction::Verify { archive } => {
+            let archives = match archive {
+                Some(archive) => vec![archive.clone()],
+                None => backup::list_backups(backup_settings)
+                    .into_iter()
+                    .map(|backup| backup.path)
+                    .collect(),
+            };
+
+            let mut failed = false;
+            for archive in archives {
+                match backup::verify_archive(&archive) {
+                    Ok(manifest) => println!("OK      {} ({} files)", archive.display(), manifest.files.len()),
+                    Err(e) => {
+                        println!("FAILED  {} - {}", archive.display(), e);
+                        failed = true;
+                    }
+                }
+            }
+            if failed {
+                exit(1)
+            }
+        }
+    }
+}
+
+async fn changes_command(args: &Args, json: bool) {
+    let snapshot_path = run_history::snapshot_path(&args.config);
+    let previous_run = run_history::load_snapshot(&snapshot_path).await;
+
+    let steam_api_client = SteamApiClient::new();
+    let (collections_children, mods_data) =
+        resolve::fetch_mods_data(&steam_api_client, &ZSO_CONFIG.collections).await;
+    let current_run = run_history::RunSnapshot::new(&collections_children, &mods_data);
+
+    match &previous_run {
+        Some(previous_run) => {
+            let change_report = run_history::ChangeReport::between(previous_run, &current_run);
+            match json {
+                true => match serde_json::to_string_pretty(&change_report) {
+                    Ok(report_json) => println!("{}", report_json),
+                    Err(e) => {
+                        error!("Failed to serialize change report - {}", e);
+                        exit(1)
+                    }
+                },
+                false => print!("{}", change_report.summary()),
+            }
+        }
+        None => {
+            info!("No previous run recorded in {}, saving this one", snapshot_path.display());
+        }
+    }
+
+    if current_run.save(&snapshot_path).await.is_err() {
+        exit(1)
+    }
+}
+
+async fn import_command(args: &Args, collections: &[u64], out: &Path, force: bool) {
+    let ini_path = match &args.ini {
+        Some(ini_path) => ini_path,
+        None => {
+            error!("import needs the server ini, pass it with --ini");
+            exit(1)
+        }
+    };
+
+    if out.exists() && !force {
+        error!("{} already exists, pass --force to overwrite it or --out to write elsewhere", out.display());
+        exit(1)
+    }
+
+    // ZSO_CONFIG would write a default config and exit when the file is missing
+    let mut base = match args.config.exists() {
+        true => ZSO_CONFIG.clone(),
+        false => ZSOConfig::default(),
+    };
+
+    for collection_id in collections {
+        if !base.collections.contains(collection_id) {
+            base.collections.push(*collection_id);
+        }
+    }
+
+    let current = zomboid_utils::ini_initial_check(ini_path);
+    let ini_items = import::parse_workshop_ids(&current.workshop_items);
+
+    let steam_api_client = SteamApiClient::new();
+
+    let collection_items = match base.collections.is_empty() {
+        true => {
+            warn!("No collections given - every workshop item goes to include");
+            vec![]
+        }
+        false => {
+            steam_api_client
+                .get_list_of_mods_in_collections(base.collections.clone())
+                .await
+        }
+    };
+
+    let mut items_to_resolve = ini_items.clone();
+    for workshop_id in &collection_items {
+        if !items_to_resolve.contains(workshop_id) {
+            items_to_resolve.push(*workshop_id);
+        }
+    }
+
+    let requested = items_to_resolve.len();
+    let mods_data = steam_api_client.resolve_mods_data(items_to_resolve).await;
+    info!("Resolved {} of {} workshop items", mods_data.len(), requested);
+
+    let imported = import::build_import_config(base, &current, &collection_items, &mods_data);
+
+    let imported_yaml = match serde_yaml::to_string(&imported) {
+        Ok(imported_yaml) => imported_yaml,
+        Err(e) => {
+            error!("Failed to serialize config - {}", e);
+            exit(1)
+        }
+    };
+
+    match tokio::fs::write(out, imported_yaml.as_bytes()).await {
+        Ok(_) => info!("Config was written to {}", out.display()),
+        Err(e) => {
+            error!("Failed to write {} - {}", out.display(), e);
+            exit(1)
+        }
+    }
+}
+
+async fn lint_command(args: &Args) {
+    let ini_path = match &args.ini {
+        Some(ini_path) => ini_path,
+        None => {
+            error!("lint needs the server ini, pass it with --ini");
+            exit(1)
+        }
+    };
+
+    let server_conf = match tokio::fs::read_to_string(ini_path).await {
+        Ok(server_conf) => server_conf,
+        Err(e) => {
+            error!("Failed to open ini file - {}", e);
+            exit(1)
+        }
+    };
+
+    // lint what the ini will look like once server_options are applied
+    let mut values = server_ini::parse_ini(&server_conf);
+    for (key, value) in &ZSO_CONFIG.server_options {
+        match values.iter_mut().find(|(existing, _)| existing == key) {
+            Some(existing) => existing.1 = value.to_string(),
+            None => values.push((key.clone(), value.to_string())),
+        }
+    }
+
+    let issues = server_ini::lint(&values, ZSO_CONFIG.rcon.as_ref());
+    server_ini::report_issues(&issues);
+
+    if issues.iter().any(|issue| issue.severity == server_ini::Severity::Error) {
+        exit(1)
+    }
+}
+
+async fn sandbox_command(args: &Args, action: &SandboxAction) {
+    let sandbox_settings = ZSO_CONFIG.sandbox.clone().unwrap_or_default();
+
+    let sandbox_path = match (&sandbox_settings.path, &args.ini) {
+        (Some(path), _) => path.clone(),
+        (None, Some(ini_path)) => sandbox_vars::sandboxvars_path(ini_path),
+        (None, None) => {
+            error!("Set sandbox.path in the config or pass --ini to locate SandboxVars.lua");
+            exit(1)
+        }
+    };
+
+    let source = match tokio::fs::read_to_string(&sandbox_path).await {
+        Ok(source) => source,
+        Err(e) => {
+            error!("Failed to open {} - {}", sandbox_path.display(), e);
+            exit(1)
+        }
+    };
+
+    let mut sandbox = match sandbox_vars::SandboxVars::parse(&source) {
+        Ok(sandbox) => sandbox,
+        Err(e) => {
+            error!("Failed to parse {} - {}", sandbox_path.display(), e);
+            exit(1)
+        }
+    };
+
+    let changes = match action {
+        SandboxAction::Get { key } => {
+            for entry in sandbox.entries() {
+                if key.is_none() || key.as_ref() == Some(&entry.key) {
+                    println!("{}={}", entry.key, entry.value);
+                }
+            }
+            return;
+        }
+        SandboxAction::Set { key, value } => vec![sandbox_vars::SandboxChange {
+            key: key.clone(),
+            current: sandbox.get(key).cloned(),
+            desired: sandbox_vars::SandboxValue::parse_cli(value),
+        }],
+        SandboxAction::Diff | SandboxAction::Apply => {
+            let desired = match sandbox_vars::desired_values(&sandbox_settings) {
+                Ok(desired) => desired,
+                Err(e) => {
+                    error!("{}", e);
+                    exit(1)
+                }
+            };
+            sandbox.diff(&desired)
+        }
+    };
+
+    for change in &changes {
+        match &change.current {
+            Some(current) => println!("{}: {} -> {}", change.key, current, change.desired),
+            None => println!("{}: (missing) -> {}", change.key, change.desired),
+        }
+    }
+
+    if let SandboxAction::Diff = action {
+        return;
+    }
+
+    if changes.is_empty() {
+        info!("SandboxVars are up to date");
+        return;
+    }
+
+    for change in &changes {
+        if let Err(e) = sandbox.set(&change.key, &change.desired) {
+            error!("Failed to set {} - {}", change.key, e);
+            exit(1)
+        }
+    }
+
+    match tokio::fs::write(&sandbox_path, sandbox.to_lua()).await {
+        Ok(_) => info!("{} was updated", sandbox_path.display()),
+        Err(e) => {
+            error!("Failed to write {} - {}", sandbox_path.display(), e);
+            exit(1)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+
+use crate::config::{MaintenanceSettings, MaintenanceWindow};
+
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|e| format!("bad time {time}, expected HH:MM - {e}"))
+}
+
+fn parse_weekday(day: &str) -> Result<Weekday, String> {
+    day.parse::<Weekday>().map_err(|_| format!("bad weekday {day}"))
+}
+
+pub fn timezone(settings: &MaintenanceSettings) -> Result<Tz, String> {
+    settings
+        .timezone
+        .parse::<Tz>()
+        .map_err(|e| format!("bad timezone {} - {}", settings.timezone, e))
+}
+
+impl MaintenanceWindow {
+    /// A window ending before it starts runs past midnight, it belongs to the day it starts on.
+    fn contains(&self, weekday: Weekday, time: NaiveTime) -> Result<bool, String> {
+        let start = parse_time(&self.start)?;
+        let end = parse_time(&self.end)?;
+        let days = self
+            .days
+            .iter()
+            .map(|day| parse_weekday(day))
+            .collect::<Result<Vec<Weekday>, String>>()?;
+        let on_day = |day: Weekday| days.is_empty() || days.contains(&day);
+
+        Ok(match start <= end {
+            true => on_day(weekday) && time >= start && time < end,
+            false => (on_day(weekday) && time >= start) || (on_day(weekday.pred()) && time < end),
+        })
+    }
+}
+
+/// Parses the timezone and every window, so mistakes show up at startup instead of when an update is waiting.
+pub fn validate(settings: &MaintenanceSettings) -> Result<(), String> {
+    timezone(settings)?;
+    for window in &settings.windows {
+        parse_time(&window.start)?;
+        parse_time(&window.end)?;
+        for day in &window.days {
+            parse_weekday(day)?;
+        }
+    }
+    Ok(())
+}
+
+/// No windows configured means any time is fine.
+pub fn in_window(settings: &MaintenanceSettings, now: DateTime<Utc>) -> Result<bool, String> {
+    if settings.windows.is_empty() {
+        return Ok(true);
+    }
+
+    let local = now.with_timezone(&timezone(settings)?);
+    for window in &settings.windows {
+        if window.contains(local.weekday(), local.time())? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Start of the next window within a week, to the minute.
+pub fn next_window(settings: &MaintenanceSettings, now: DateTime<Utc>) -> Result<Option<DateTime<Tz>>, String> {
+    let tz = timezone(settings)?;
+    let mut time = now.with_second(0).and_then(|time| time.with_nanosecond(0)).unwrap_or(now);
+
+    for _ in 0..7 * 24 * 60 {
+        time += Duration::minutes(1);
+        if in_window(settings, time)? {
+            return Ok(Some(time.with_timezone(&tz)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maintenance_window_test() {
+        let settings = MaintenanceSettings {
+            timezone: "Europe/Berlin".to_owned(),
+            windows: vec![MaintenanceWindow {
+                days: vec!["sat".to_owned(), "sun".to_owned()],
+                start: "23:00".to_owned(),
+                end: "02:00".to_owned(),
+            }],
+            emergency_override: true,
+        };
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+
+        // Saturday 23:30 in Berlin (CEST, +2)
+        assert!(in_window(&settings, at("2026-10-17T21:30:00Z")).unwrap());
+        // Monday 01:00, the window started on Sunday
+        assert!(in_window(&settings, at("2026-10-18T23:00:00Z")).unwrap());
+        // Tuesday 01:00
+        assert!(!in_window(&settings, at("2026-10-19T23:00:00Z")).unwrap());
+        assert_eq!(
+            at("2026-10-24T21:00:00Z"),
+            next_window(&settings, at("2026-10-20T10:00:00Z")).unwrap().unwrap()
+        );
+    }
+
+    #[test]
+    fn validate_test() {
+        let mut settings = MaintenanceSettings {
+            timezone: "Europe/Berlin".to_owned(),
+            windows: vec![MaintenanceWindow {
+                days: vec!["sat".to_owned()],
+                start: "23:00".to_owned(),
+                end: "02:00".to_owned(),
+            }],
+            emergency_override: true,
+        };
+        assert_eq!(Ok(()), validate(&settings));
+
+        settings.windows[0].days.push("caturday".to_owned());
+        assert!(validate(&settings).is_err());
+        settings.windows[0].days.clear();
+        settings.windows[0].end = "25:00".to_owned();
+        assert!(validate(&settings).is_err());
+        settings.windows[0].end = "02:00".to_owned();
+        settings.timezone = "Mars/Olympus".to_owned();
+        assert!(validate(&settings).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use log::{info, warn};
+
+use crate::config::ConfigWorkshopSettings;
+use crate::steam_api_client::ModData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    ModId,
+    MapFolder,
+}
+
+impl std::fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConflictKind::ModId => write!(f, "Mod ID"),
+            ConflictKind::MapFolder => write!(f, "map folder"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub name: String,
+    pub workshop_items: Vec<u64>,
+    pub winner: Option<u64>,
+}
+
+impl Conflict {
+    pub fn is_resolved(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    pub fn losers(&self) -> Vec<u64> {
+        match self.winner {
+            Some(winner) => self
+                .workshop_items
+                .iter()
+                .filter(|id| **id != winner)
+                .copied()
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+/// Finds Mod IDs and map folders declared by more than one workshop item.
+/// Excluded workshop items, mods and maps are ignored, they never reach the ini anyway.
+pub(crate) fn detect_conflicts(
+    mods_data: &[ModData],
+    workshop_settings: &ConfigWorkshopSettings,
+) -> Vec<Conflict> {
+    let exclude = &workshop_settings.exclude;
+    let mut mod_owners: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    let mut map_owners: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+
+    for mod_data in mods_data {
+        if exclude.workshop_items.contains(&mod_data.mod_id) {
+            continue;
+        }
+
+        for mod_name in &mod_data.mod_name {
+            if exclude.mods.contains(mod_name) {
+                continue;
+            }
+            let owners = mod_owners.entry(mod_name.clone()).or_default();
+            if !owners.contains(&mod_data.mod_id) {
+                owners.push(mod_data.mod_id);
+            }
+        }
+
+        for map_name in &mod_data.map_name {
+            if exclude.maps.contains(map_name) {
+                continue;
+            }
+            let owners = map_owners.entry(map_name.clone()).or_default();
+            if !owners.contains(&mod_data.mod_id) {
+                owners.push(mod_data.mod_id);
+            }
+        }
+    }
+
+    let mut conflicts: Vec<Conflict> = vec![];
+
+    for (kind, owners_map, winners) in [
+        (ConflictKind::ModId, mod_owners, &workshop_settings.conflicts.mods),
+        (ConflictKind::MapFolder, map_owners, &workshop_settings.conflicts.maps),
+    ] {
+        for (name, workshop_items) in owners_map {
+            if workshop_items.len() < 2 {
+                continue;
+            }
+
+            let winner = match winners.get(&name) {
+                Some(winner) if workshop_items.contains(winner) => Some(*winner),
+                Some(winner) => {
+                    warn!(
+                        "Configured winner {} for {} {} is not one of the colliding items {:?}",
+                        winner, kind, name, workshop_items
+                    );
+                    None
+                }
+                None => None,
+            };
+
+            conflicts.push(Conflict {
+                kind,
+                name,
+                workshop_items,
+                winner,
+            });
+        }
+    }
+
+    conflicts
+}
+
+pub(crate) fn report_conflicts(conflicts: &[Conflict], mods_data: &[ModData]) {
+    if conflicts.is_empty() {
+        info!("No Mod ID or map folder conflicts found");
+        return;
+    }
+
+    for conflict in conflicts {
+        let items = conflict
+            .workshop_items
+            .iter()
+            .map(|id| describe_item(*id, mods_data))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        match conflict.winner {
+            Some(winner) => info!(
+                "{} {} is declared by {} - using {}",
+                conflict.kind, conflict.name, items, winner
+            ),
+            None => warn!(
+                "{} {} is declared by {} - unresolved, set workshop_settings.conflicts to pick one",
+                conflict.kind, conflict.name, items
+            ),
+        }
+    }
+}
+
+/// Takes the colliding Mod ID or map folder away from every item that lost a resolved conflict,
+/// so the winner is the only item left providing it. Items left with nothing to provide are dropped.
+pub(crate) fn apply_resolutions(mods_data: Vec<ModData>, conflicts: &[Conflict]) -> Vec<ModData> {
+    mods_data
+        .into_iter()
+        .filter_map(|mut mod_data| {
+            let lost = conflicts
+                .iter()
+                .filter(|conflict| conflict.losers().contains(&mod_data.mod_id))
+                .collect::<Vec<&Conflict>>();
+            if lost.is_empty() {
+                return Some(mod_data);
+            }
+
+            for conflict in lost {
+                info!(
+                    "Workshop item {} no longer provides {} {} - it lost the conflict to {}",
+                    mod_data.mod_id,
+                    conflict.kind,
+                    conflict.name,
+                    conflict.winner.unwrap_or_default()
+                );
+                match conflict.kind {
+                    ConflictKind::ModId => mod_data.mod_name.retain(|mod_name| *mod_name != conflict.name),
+                    ConflictKind::MapFolder => mod_data.map_name.retain(|map_name| *map_name != conflict.name),
+                }
+            }
+
+            if mod_data.mod_name.is_empty() && mod_data.map_name.is_empty() {
+                warn!("Dropping workshop item {} - every mod and map it provides lost a conflict", mod_data.mod_id);
+                return None;
+            }
+            Some(mod_data)
+        })
+        .collect()
+}
+
+pub(crate) fn unresolved_count(conflicts: &[Conflict]) -> usize {
+    conflicts.iter().filter(|c| !c.is_resolved()).count()
+}
+
+/// Whether `fail_on_unresolved` keeps the server ini from being updated.
+pub(crate) fn blocks_ini_update(conflicts: &[Conflict], workshop_settings: &ConfigWorkshopSettings) -> bool {
+    workshop_settings.conflicts.fail_on_unresolved && unresolved_count(conflicts) > 0
+}
+
+fn describe_item(workshop_id: u64, mods_data: &[ModData]) -> String {
+    match mods_data.iter().find(|m| m.mod_id == workshop_id) {
+        Some(mod_data) => format!("{} ({})", workshop_id, mod_data.mod_name.join(";")),
+        None => workshop_id.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(workshop_id: u64, mods: &[&str], maps: &[&str]) -> ModData {
+        ModData {
+            mod_id: workshop_id,
+            mod_name: mods.iter().map(|name| name.to_string()).collect(),
+            map_name: maps.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn mods_data() -> Vec<ModData> {
+        vec![
+            item(1, &["Hydrocraft", "HydroExtras"], &[]),
+            item(2, &["Hydrocraft"], &[]),
+            item(3, &["Bedford"], &["Bedford Falls"]),
+            item(4, &[], &["Bedford Falls"]),
+        ]
+    }
+
+    #[test]
+    fn detect_conflicts_test() {
+        let mut workshop_settings = ConfigWorkshopSettings::default();
+        workshop_settings.conflicts.mods.insert("Hydrocraft".to_owned(), 2);
+        workshop_settings.conflicts.maps.insert("Bedford Falls".to_owned(), 99);
+
+        let conflicts = detect_conflicts(&mods_data(), &workshop_settings);
+
+        assert_eq!(
+            vec![
+                Conflict {
+                    kind: ConflictKind::ModId,
+                    name: "Hydrocraft".to_owned(),
+                    workshop_items: vec![1, 2],
+                    winner: Some(2),
+                },
+                // 99 isn't one of the colliding items
+                Conflict {
+                    kind: ConflictKind::MapFolder,
+                    name: "Bedford Falls".to_owned(),
+                    workshop_items: vec![3, 4],
+                    winner: None,
+                },
+            ],
+            conflicts
+        );
+
+        // excluded items don't collide
+        workshop_settings.exclude.workshop_items.push(4);
+        assert_eq!(1, detect_conflicts(&mods_data(), &workshop_settings).len());
+    }
+
+    #[test]
+    fn apply_resolutions_test() {
+        let mut workshop_settings = ConfigWorkshopSettings::default();
+        workshop_settings.conflicts.mods.insert("Hydrocraft".to_owned(), 2);
+        workshop_settings.conflicts.maps.insert("Bedford Falls".to_owned(), 3);
+        let conflicts = detect_conflicts(&mods_data(), &workshop_settings);
+
+        let resolved = apply_resolutions(mods_data(), &conflicts);
+
+        // 1 keeps its other mod, 4 had nothing else to provide
+        assert_eq!(vec![1, 2, 3], resolved.iter().map(|mod_data| mod_data.mod_id).collect::<Vec<u64>>());
+        assert_eq!(vec!["HydroExtras"], resolved[0].mod_name);
+        assert_eq!(vec!["Hydrocraft"], resolved[1].mod_name);
+        assert_eq!(vec!["Bedford Falls"], resolved[2].map_name);
+    }
+
+    #[test]
+    fn fail_on_unresolved_test() {
+        let mut workshop_settings = ConfigWorkshopSettings::default();
+        workshop_settings.conflicts.mods.insert("Hydrocraft".to_owned(), 1);
+        let conflicts = detect_conflicts(&mods_data(), &workshop_settings);
+
+        assert_eq!(1, unresolved_count(&conflicts));
+        assert!(!blocks_ini_update(&conflicts, &workshop_settings));
+
+        workshop_settings.conflicts.fail_on_unresolved = true;
+        assert!(blocks_ini_update(&conflicts, &workshop_settings));
+
+        workshop_settings.conflicts.maps.insert("Bedford Falls".to_owned(), 4);
+        let conflicts = detect_conflicts(&mods_data(), &workshop_settings);
+        assert!(!blocks_ini_update(&conflicts, &workshop_settings));
+    }
+
+    #[test]
+    fn partial_conflict_settings_test() {
+        let workshop_settings: ConfigWorkshopSettings = serde_yaml::from_str(
+            "
+include: { workshop_items: [], mods: [], maps: [] }
+exclude: { workshop_items: [], mods: [], maps: [] }
+conflicts: { fail_on_unresolved: true }
+",
+        )
+        .unwrap();
+
+        assert!(workshop_settings.conflicts.fail_on_unresolved);
+        assert!(workshop_settings.conflicts.mods.is_empty());
+        assert!(workshop_settings.conflicts.maps.is_empty());
+    }
+}
@@ -0,0 +1,318 @@
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::warn;
+use serde_derive::Serialize;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+
+use crate::config::{NotificationSettings, NotificationSink, NotifyEvent, SinkTarget, SmtpTls, ZSOConfig};
+use crate::template::{Template, TemplateContext, TemplateValue};
+
+pub const DEFAULT_TEMPLATE: &str = "{{ title }}: {{ message }}{{#each details}}\n- {{ this }}{{/each}}";
+/// A sink that doesn't answer in time is given up on, notifications never hold up the operator.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl NotifyEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyEvent::Update => "update",
+            NotifyEvent::Reboot => "reboot",
+            NotifyEvent::RebootFailed => "reboot_failed",
+            NotifyEvent::Rollback => "rollback",
+            NotifyEvent::PreflightFailed => "preflight_failed",
+            NotifyEvent::BackupFailed => "backup_failed",
+            NotifyEvent::Crash => "crash",
+            NotifyEvent::TaskFailed => "task_failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub event: NotifyEvent,
+    pub title: String,
+    pub message: String,
+    pub details: Vec<String>,
+    pub time: i64,
+}
+
+impl Notification {
+    pub fn new(event: NotifyEvent, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            event,
+            title: title.into(),
+            message: message.into(),
+            details: vec![],
+            time: Utc::now().timestamp(),
+        }
+    }
+
+    pub fn details<T: std::fmt::Display>(mut self, details: &[T]) -> Self {
+        self.details = details.iter().map(ToString::to_string).collect();
+        self
+    }
+
+    fn context(&self) -> TemplateContext {
+        let mut context = TemplateContext::new();
+        context.insert("event".to_owned(), TemplateValue::text(self.event.name()));
+        context.insert("title".to_owned(), TemplateValue::text(&self.title));
+        context.insert("message".to_owned(), TemplateValue::text(&self.message));
+        context.insert("details".to_owned(), TemplateValue::list(&self.details));
+        let time = chrono::DateTime::from_timestamp(self.time, 0).unwrap_or_default();
+        context.insert("time".to_owned(), TemplateValue::text(time.format("%Y-%m-%d %H:%M:%S UTC")));
+        context
+    }
+}
+
+impl NotificationSink {
+    pub fn wants(&self, event: NotifyEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+
+    pub fn render(&self, notification: &Notification) -> Result<String, String> {
+        let template = Template::parse(self.template.as_deref().unwrap_or(DEFAULT_TEMPLATE))?;
+        Ok(template.render(&notification.context()))
+    }
+}
+
+async fn post_json(client: &reqwest::Client, url: &str, headers: &[(&String, &String)], body: serde_json::Value) -> Result<(), String> {
+    let mut request = client.post(url).json(&body);
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    match response.status().is_success() {
+        true => Ok(()),
+        false => Err(format!("{} answered {}", url, response.status())),
+    }
+}
+
+async fn send_email(target: &SinkTarget, subject: &str, text: &str) -> Result<(), String> {
+    let (host, port, tls, username, password, from, to) = match target {
+        SinkTarget::Email {
+            host,
+            port,
+            tls,
+            username,
+            password,
+            from,
+            to,
+        } => (host, *port, tls, username, password, from, to),
+        _ => return Err("not an email sink".to_owned()),
+    };
+
+    let mut builder = Message::builder()
+        .from(from.parse::<Mailbox>().map_err(|e| format!("bad from address {from} - {e}"))?)
+        .subject(subject);
+    for to in to {
+        builder = builder.to(to.parse::<Mailbox>().map_err(|e| format!("bad to address {to} - {e}"))?);
+    }
+    let email = builder.body(text.to_owned()).map_err(|e| e.to_string())?;
+
+    let mut transport = match tls {
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host).map_err(|e| e.to_string())?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(|e| e.to_string())?,
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+    }
+    .port(port)
+    .timeout(Some(SEND_TIMEOUT));
+    if let (Some(username), Some(password)) = (username, password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    transport.build().send(email).await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+async fn run_command(command: &str, notification: &Notification, text: &str) -> Result<(), String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("ZSO_EVENT", notification.event.name())
+        .env("ZSO_TITLE", &notification.title)
+        .env("ZSO_MESSAGE", &notification.message)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to run {command} - {e}"))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes()).await;
+    }
+
+    match tokio::time::timeout(SEND_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(format!("{command} exited with {status}")),
+        Ok(Err(e)) => Err(format!("failed to run {command} - {e}")),
+        Err(_) => Err(format!("{command} didn't finish within {}s", SEND_TIMEOUT.as_secs())),
+    }
+}
+
+async fn send(client: &reqwest::Client, sink: &NotificationSink, notification: &Notification) -> Result<(), String> {
+    let text = sink.render(notification)?;
+
+    match &sink.target {
+        SinkTarget::Discord { url } => post_json(client, url, &[], json!({ "content": text })).await,
+        SinkTarget::Slack { url } => post_json(client, url, &[], json!({ "text": text })).await,
+        SinkTarget::Webhook { url, headers } => {
+            let mut body = serde_json::to_value(notification).map_err(|e| e.to_string())?;
+            body["text"] = json!(text);
+            post_json(client, url, &headers.iter().collect::<Vec<_>>(), body).await
+        }
+        SinkTarget::Email { .. } => send_email(&sink.target, &notification.title, &text).await,
+        SinkTarget::Command { command } => run_command(command, notification, &text).await,
+    }
+}
+
+/// Background sends still going, `flush` waits for them before zso exits.
+static PENDING: Mutex<Vec<JoinHandle<()>>> = Mutex::new(vec![]);
+
+/// Sends to every sink that takes the event at the same time. Failed sinks are only logged.
+async fn send_all(settings: NotificationSettings, notification: Notification) {
+    let client = match reqwest::Client::builder().timeout(SEND_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to set up notifications - {}", e);
+            return;
+        }
+    };
+
+    let mut sends = vec![];
+    for (index, sink) in settings.sinks.into_iter().enumerate() {
+        if !sink.wants(notification.event) {
+            continue;
+        }
+        let (client, notification) = (client.clone(), notification.clone());
+        sends.push(tokio::spawn(async move {
+            if let Err(e) = send(&client, &sink, &notification).await {
+                warn!("Notification sink {} failed to send {} - {}", index + 1, notification.event.name(), e);
+            }
+        }));
+    }
+
+    for send in sends {
+        let _ = send.await;
+    }
+}
+
+/// Sends `notification` to every sink that takes its event and waits for them.
+pub async fn notify(config: &ZSOConfig, notification: Notification) {
+    if let Some(settings) = &config.notifications {
+        send_all(settings.clone(), notification).await;
+    }
+}
+
+/// Like `notify`, without waiting for the sinks. For reboots, crashes and rollbacks that shouldn't wait on a slow sink.
+pub fn notify_in_background(config: &ZSOConfig, notification: Notification) {
+    if let Some(settings) = &config.notifications {
+        let handle = tokio::spawn(send_all(settings.clone(), notification));
+        let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|handle| !handle.is_finished());
+        pending.push(handle);
+    }
+}
+
+/// Waits for background notifications, each sink gives up after `SEND_TIMEOUT`.
+pub async fn flush() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+    for handle in pending {
+        let _ = handle.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// Answers every request with 204 and hands out the request bodies.
+    async fn http_listener() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (body_tx, body_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![];
+                let mut buffer = [0; 4096];
+                loop {
+                    let read = stream.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|len| len.trim().to_owned()))
+                            .and_then(|len| len.parse::<usize>().ok())
+                            .unwrap_or_default();
+                        if body.len() >= length {
+                            body_tx.send(body.to_owned()).unwrap();
+                            break;
+                        }
+                    }
+                }
+                stream.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+            }
+        });
+
+        (url, body_rx)
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_test() {
+        let (url, mut bodies) = http_listener().await;
+        let config: ZSOConfig = serde_yaml::from_str(&format!(
+            "
+collections: []
+workshop_settings:
+  include: {{ workshop_items: [], mods: [], maps: [] }}
+  exclude: {{ workshop_items: [], mods: [], maps: [] }}
+notifications:
+  sinks:
+    - type: discord
+      url: {url}
+      events: [reboot_failed]
+    - type: webhook
+      url: {url}
+      template: '{{{{ event }}}} {{{{ details | join \", \" }}}}'
+"
+        ))
+        .unwrap();
+
+        notify(
+            &config,
+            Notification::new(NotifyEvent::Update, "Update", "2 workshop items changed").details(&["Brita", "Arsenal"]),
+        )
+        .await;
+        let webhook: serde_json::Value = serde_json::from_str(&bodies.recv().await.unwrap()).unwrap();
+        assert_eq!("update", webhook["event"]);
+        assert_eq!("update Brita, Arsenal", webhook["text"]);
+        // the update only went to the webhook, the discord sink just takes failed reboots
+        assert!(bodies.try_recv().is_err());
+
+        notify_in_background(&config, Notification::new(NotifyEvent::RebootFailed, "Reboot failed", "exit status 1"));
+        flush().await;
+
+        // both sinks send at the same time, in no particular order
+        let mut sent = vec![];
+        while let Ok(body) = bodies.try_recv() {
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            sent.push(match body.get("content") {
+                Some(content) => format!("discord {}", content.as_str().unwrap()),
+                None => format!("webhook {}", body["event"].as_str().unwrap()),
+            });
+        }
+        sent.sort();
+        assert_eq!(vec!["discord Reboot failed: exit status 1", "webhook reboot_failed"], sent);
+        assert!(bodies.try_recv().is_err());
+    }
+}
@@ -0,0 +1,746 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+use crate::backup;
+use crate::config::{NotifyEvent, RebootPolicy, ServerSettings, ZSOConfig};
+use crate::game_build;
+use crate::log_parser::{LogTailer, ServerEvent};
+use crate::maintenance;
+use crate::mod_conflicts;
+use crate::notify::{self, Notification};
+use crate::rcon::{self, RconClient};
+use crate::reboot_messages::{self, RebootNotice};
+use crate::resolve;
+use crate::steam_api_client::SteamApiClient;
+use crate::workshop_state::{self, StaleItem};
+
+/// Why the server needs a restart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateReason {
+    Mods(Vec<StaleItem>),
+    GameBuild { branch: String, installed: u64, latest: u64 },
+    /// The server itself logged that a workshop item is out of date.
+    ServerReported { workshop_id: Option<u64>, message: String },
+}
+
+impl std::fmt::Display for UpdateReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateReason::Mods(stale_items) => {
+                let titles = stale_items
+                    .iter()
+                    .map(|item| item.title.as_str())
+                    .collect::<Vec<&str>>();
+                write!(f, "{} workshop items updated: {}", stale_items.len(), titles.join(", "))
+            }
+            UpdateReason::GameBuild {
+                branch,
+                installed,
+                latest,
+            } => write!(f, "dedicated server build {installed} -> {latest} on {branch}"),
+            UpdateReason::ServerReported {
+                workshop_id: Some(workshop_id),
+                ..
+            } => write!(f, "server reports workshop item {workshop_id} is out of date"),
+            UpdateReason::ServerReported { message, .. } => write!(f, "server reports outdated mods: {message}"),
+        }
+    }
+}
+
+/// Resolved items whose downloaded copy is behind the workshop.
+pub async fn find_stale_mods(config: &ZSOConfig) -> Result<Vec<StaleItem>, ()> {
+    let acf_path = match workshop_state::appworkshop_path(config) {
+        Some(acf_path) => acf_path,
+        None => {
+            error!("Can't find the workshop acf - set workshop_settings.content_dir or steamcmd.install_dir");
+            return Err(());
+        }
+    };
+    let app_workshop = workshop_state::load_appworkshop(&acf_path).await?;
+
+    let steam_api_client = SteamApiClient::new();
+    let (_, mods_data) = resolve::fetch_mods_data(&steam_api_client, &config.collections).await;
+    if mods_data.is_empty() {
+        error!("No mods resolved from the collections, can't compare with the downloaded items");
+        return Err(());
+    }
+    let conflicts = mod_conflicts::detect_conflicts(&mods_data, &config.workshop_settings);
+    let mods_data = mod_conflicts::apply_resolutions(mods_data, &conflicts)
+        .into_iter()
+        .filter(|mod_data| !config.workshop_settings.exclude.workshop_items.contains(&mod_data.mod_id))
+        .collect::<Vec<_>>();
+
+    Ok(workshop_state::find_stale_items(&app_workshop, &mods_data))
+}
+
+/// Compares the installed dedicated server build with the latest one of its branch.
+pub async fn check_game_update(config: &ZSOConfig, server_settings: &ServerSettings) -> Option<UpdateReason> {
+    let install_dir = server_settings.install_dir.as_ref()?;
+    let installed = game_build::load_installed_build(install_dir).await.ok()?;
+
+    match game_build::latest_build(
+        &server_settings.build_source,
+        config.steamcmd.as_ref(),
+        &installed.branch,
+    )
+    .await
+    {
+        Ok(Some(latest)) if latest != installed.build_id => Some(UpdateReason::GameBuild {
+            branch: installed.branch,
+            installed: installed.build_id,
+            latest,
+        }),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Failed to get the latest dedicated server build - {}", e);
+            None
+        }
+    }
+}
+
+pub async fn check_for_updates(config: &ZSOConfig, server_settings: &ServerSettings) -> Vec<UpdateReason> {
+    let mut reasons = vec![];
+
+    match find_stale_mods(config).await {
+        Ok(stale_items) if !stale_items.is_empty() => reasons.push(UpdateReason::Mods(stale_items)),
+        Ok(_) => {}
+        Err(_) => warn!("Skipping the workshop item check"),
+    }
+
+    if let Some(reason) = check_game_update(config, server_settings).await {
+        reasons.push(reason);
+    }
+
+    reasons
+}
+
+async fn broadcast(config: &ZSOConfig, message: &str) {
+    let rcon_settings = match &config.rcon {
+        Some(rcon_settings) => rcon_settings,
+        None => return,
+    };
+
+    let result = match RconClient::connect(rcon_settings).await {
+        Ok(mut client) => client.broadcast(message).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        warn!("Failed to broadcast \"{}\" - {}", message, e);
+    }
+}
+
+/// `None` when RCON isn't configured or the server doesn't answer.
+async fn players_online(config: &ZSOConfig) -> Option<u32> {
+    let rcon_settings = config.rcon.as_ref()?;
+    match rcon::players_online(rcon_settings).await {
+        Ok(players) => Some(players),
+        Err(e) => {
+            warn!("Failed to get the player count - {}", e);
+            None
+        }
+    }
+}
+
+/// Players who asked for the reboot to be postponed during the current countdown.
+struct PostponeVote {
+    command: String,
+    voters: Vec<String>,
+}
+
+impl PostponeVote {
+    async fn passed(&self, config: &ZSOConfig) -> bool {
+        if self.voters.is_empty() {
+            return false;
+        }
+        vote_passed(self.voters.len(), players_online(config).await)
+    }
+}
+
+/// More than half of the players online have to vote. Without a player count the vote fails,
+/// a single voter would carry it otherwise.
+fn vote_passed(voters: usize, players: Option<u32>) -> bool {
+    match players {
+        Some(players) => voters > 0 && voters as u32 * 2 > players,
+        None => false,
+    }
+}
+
+/// Sleeps while following server events, chat votes are counted when a vote is running.
+async fn wait_following_events(
+    duration: Duration,
+    events: &mut Option<mpsc::UnboundedReceiver<ServerEvent>>,
+    vote: &mut Option<PostponeVote>,
+) {
+    let sleep = tokio::time::sleep(duration);
+    tokio::pin!(sleep);
+
+    loop {
+        let event = tokio::select! {
+            _ = &mut sleep => return,
+            event = next_event(events) => event,
+        };
+        let event = match event {
+            Some(event) => event,
+            None => {
+                *events = None;
+                continue;
+            }
+        };
+        log_event(&event);
+
+        if let (Some(vote), ServerEvent::Chat { author, text, .. }) = (vote.as_mut(), &event) {
+            if text.trim().eq_ignore_ascii_case(&vote.command) && !vote.voters.contains(author) {
+                info!("{} voted to postpone the reboot", author);
+                vote.voters.push(author.clone());
+            }
+        }
+    }
+}
+
+/// Warns players over RCON at every countdown step, without RCON messages it just waits `reboot_delay_sec`.
+/// Returns false when players voted to postpone the reboot.
+async fn countdown(
+    config: &ZSOConfig,
+    server_settings: &ServerSettings,
+    notice: &RebootNotice,
+    events: &mut Option<mpsc::UnboundedReceiver<ServerEvent>>,
+    vote: &mut Option<PostponeVote>,
+    vote_message: &str,
+) -> bool {
+    match (&config.rcon, server_settings.rcon_messages) {
+        (Some(rcon_settings), true) => {
+            let messages = &rcon_settings.messages;
+            let steps = reboot_messages::countdown_steps(messages);
+
+            for (index, step) in steps.iter().enumerate() {
+                for message in reboot_messages::step_messages(messages, step, notice) {
+                    broadcast(config, &message).await;
+                }
+                if index == 0 && vote.is_some() {
+                    broadcast(config, vote_message).await;
+                }
+                let next_minutes = steps.get(index + 1).map(|next| next.minutes).unwrap_or(0);
+                info!("Rebooting in {} minutes", step.minutes);
+                wait_following_events(Duration::from_secs((step.minutes - next_minutes) * 60), events, vote).await;
+
+                if let Some(vote) = vote {
+                    if vote.passed(config).await {
+                        return false;
+                    }
+                }
+            }
+        }
+        _ => {
+            info!("Rebooting in {} seconds", server_settings.reboot_delay_sec);
+            wait_following_events(Duration::from_secs(server_settings.reboot_delay_sec), events, &mut None).await;
+        }
+    }
+
+    true
+}
+
+/// Reboots the server following `reboot_policy`. Nobody online means rebooting right away.
+pub async fn reboot_server(
+    config: &ZSOConfig,
+    server_settings: &ServerSettings,
+    notice: &RebootNotice,
+    events: &mut Option<mpsc::UnboundedReceiver<ServerEvent>>,
+) -> Result<(), ()> {
+    let mut postpones = 0;
+
+    loop {
+        match players_online(config).await {
+            Some(0) => {
+                info!("Nobody is online, rebooting now");
+                return run_reboot_command(config, server_settings).await;
+            }
+            Some(players) => info!("{} players online", players),
+            None => {}
+        }
+
+        match &server_settings.reboot_policy {
+            RebootPolicy::Countdown => {
+                countdown(config, server_settings, notice, events, &mut None, "").await;
+            }
+            RebootPolicy::WaitForEmpty { max_delay_sec } => {
+                info!("Waiting up to {}s for the server to empty", max_delay_sec);
+                let started = tokio::time::Instant::now();
+                while started.elapsed() < Duration::from_secs(*max_delay_sec) {
+                    wait_following_events(Duration::from_secs(30), events, &mut None).await;
+                    if players_online(config).await == Some(0) {
+                        info!("Server is empty, rebooting now");
+                        return run_reboot_command(config, server_settings).await;
+                    }
+                }
+                countdown(config, server_settings, notice, events, &mut None, "").await;
+            }
+            RebootPolicy::Vote {
+                command,
+                postpone_sec,
+                max_postpones,
+                vote_message,
+                postponed_message,
+            } => {
+                let mut vote = (postpones < *max_postpones).then(|| PostponeVote {
+                    command: command.clone(),
+                    voters: vec![],
+                });
+                if vote.is_some() && events.is_none() {
+                    warn!("Not following the server logs, players can't vote to postpone this reboot - reboot_policy vote needs server_settings.zomboid_dir");
+                }
+
+                if !countdown(config, server_settings, notice, events, &mut vote, vote_message).await {
+                    postpones += 1;
+                    info!("Players voted to postpone the reboot by {}s", postpone_sec);
+                    broadcast(config, postponed_message).await;
+                    wait_following_events(Duration::from_secs(*postpone_sec), events, &mut None).await;
+                    continue;
+                }
+            }
+        }
+
+        return run_reboot_command(config, server_settings).await;
+    }
+}
+
+/// Takes a backup first when backups are configured, a failed backup doesn't hold up the reboot.
+pub async fn run_reboot_command(config: &ZSOConfig, server_settings: &ServerSettings) -> Result<(), ()> {
+    if server_settings.reboot_command.is_empty() {
+        let failure = "server_settings.reboot_command is empty - can't reboot the server";
+        error!("{}", failure);
+        notify::notify_in_background(config, Notification::new(NotifyEvent::RebootFailed, "Reboot failed", failure));
+        return Err(());
+    }
+
+    if backup::backup_if_configured(config, "reboot").await.is_err() {
+        warn!("Rebooting without a backup");
+    }
+
+    info!("Running reboot command: {}", server_settings.reboot_command);
+    notify::notify_in_background(config, Notification::new(NotifyEvent::Reboot, "Server is rebooting", "Running the reboot command"));
+    let failure = match Command::new("sh")
+        .arg("-c")
+        .arg(&server_settings.reboot_command)
+        .status()
+        .await
+    {
+        Ok(status) if status.success() => return Ok(()),
+        Ok(status) => format!("Reboot command exited with {status}"),
+        Err(e) => format!("Failed to run reboot command - {e}"),
+    };
+
+    error!("{}", failure);
+    notify::notify_in_background(config, Notification::new(NotifyEvent::RebootFailed, "Reboot failed", failure));
+    Err(())
+}
+
+fn log_event(event: &ServerEvent) {
+    match event {
+        ServerEvent::ServerStarted => info!("Server started"),
+        ServerEvent::ModLoadError { message, .. } => warn!("Mod failed to load - {}", message),
+        ServerEvent::WorkshopOutOfDate { message, .. } => warn!("Server reports an outdated workshop item - {}", message),
+        ServerEvent::PlayerConnected { name, .. } => info!("{} connected", name),
+        ServerEvent::PlayerDisconnected { name, .. } => info!("{} disconnected", name),
+        ServerEvent::CrashStackTrace { exception, .. } => error!("Server threw {}", exception),
+        ServerEvent::Chat { .. } | ServerEvent::AdminCommand { .. } => {}
+    }
+}
+
+impl UpdateReason {
+    /// Reason key for the countdown messages, see `rcon.messages.reasons`.
+    fn reboot_reason_key(&self) -> &'static str {
+        match self {
+            UpdateReason::Mods(_) => "mod_update",
+            UpdateReason::GameBuild { .. } => "game_update",
+            UpdateReason::ServerReported { .. } => "outdated_mod",
+        }
+    }
+
+    /// Updates that keep players from joining until the server restarts.
+    pub fn breaks_joining(&self) -> bool {
+        matches!(self, UpdateReason::GameBuild { .. } | UpdateReason::ServerReported { .. })
+    }
+}
+
+/// Updates waiting for their reboot, collected over the debounce period.
+#[derive(Default, Debug)]
+struct PendingUpdates {
+    reasons: Vec<UpdateReason>,
+    since: Option<Instant>,
+    held: bool,
+}
+
+impl PendingUpdates {
+    fn add(&mut self, reason: UpdateReason) {
+        // polls keep finding the same updates while they wait
+        if !self.reasons.contains(&reason) {
+            info!("Update detected - {}", reason);
+        }
+
+        match reason {
+            UpdateReason::Mods(stale_items) => match self
+                .reasons
+                .iter_mut()
+                .find(|pending| matches!(pending, UpdateReason::Mods(_)))
+            {
+                Some(UpdateReason::Mods(pending_items)) => {
+                    for stale_item in stale_items {
+                        match pending_items.iter_mut().find(|item| item.workshop_id == stale_item.workshop_id) {
+                            Some(pending_item) => *pending_item = stale_item,
+                            None => pending_items.push(stale_item),
+                        }
+                    }
+                }
+                _ => self.reasons.push(UpdateReason::Mods(stale_items)),
+            },
+            UpdateReason::GameBuild { .. } => {
+                self.reasons.retain(|pending| !matches!(pending, UpdateReason::GameBuild { .. }));
+                self.reasons.push(reason);
+            }
+            UpdateReason::ServerReported { .. } => {
+                if !self.reasons.contains(&reason) {
+                    self.reasons.push(reason);
+                }
+            }
+        }
+
+        self.since.get_or_insert_with(Instant::now);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.reasons.is_empty()
+    }
+
+    fn is_due(&self, debounce: Duration) -> bool {
+        self.since.is_some_and(|since| since.elapsed() >= debounce)
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+fn reboot_notice(reasons: &[UpdateReason]) -> RebootNotice {
+    let mut notice = RebootNotice::default();
+
+    for reason in reasons {
+        let key = reason.reboot_reason_key().to_owned();
+        if !notice.reasons.contains(&key) {
+            notice.reasons.push(key);
+        }
+        if let UpdateReason::Mods(stale_items) = reason {
+            notice.mods.extend(stale_items.iter().map(|item| item.title.clone()));
+        }
+    }
+
+    notice
+}
+
+/// Whether the maintenance windows let the pending updates reboot the server at `now`.
+/// The windows were validated at startup.
+fn maintenance_allows(server_settings: &ServerSettings, pending: &mut PendingUpdates, now: DateTime<Utc>) -> bool {
+    let maintenance = match &server_settings.maintenance {
+        Some(maintenance) => maintenance,
+        None => return true,
+    };
+
+    if maintenance.emergency_override && pending.reasons.iter().any(UpdateReason::breaks_joining) {
+        info!("Update keeps players from joining - rebooting outside the maintenance windows");
+        return true;
+    }
+
+    match maintenance::in_window(maintenance, now) {
+        Ok(true) => true,
+        Ok(false) => {
+            if !pending.held {
+                pending.held = true;
+                match maintenance::next_window(maintenance, now) {
+                    Ok(Some(next_window)) => info!("Holding the reboot until the maintenance window at {}", next_window),
+                    _ => warn!("Holding the reboot, no maintenance window in the next week"),
+                }
+            }
+            false
+        }
+        Err(e) => {
+            error!("Invalid maintenance window, holding the reboot - {}", e);
+            false
+        }
+    }
+}
+
+/// Polls for mod and server updates and reboots the server when there are any.
+/// With `zomboid_dir` set the server logs are followed in between, and the server
+/// reporting an outdated workshop item counts as an update too. Updates are collected
+/// for `update_debounce_sec` and wait for a maintenance window when those are set.
+pub async fn watch(config: &ZSOConfig, server_settings: &ServerSettings, once: bool) {
+    // the same update showing up again right after a reboot means the server didn't pick it up,
+    // rebooting over and over won't help
+    let mut last_handled: Vec<UpdateReason> = vec![];
+    // the server keeps logging the outdated item until it restarts, and the log lines
+    // written during the countdown are only read after it
+    let mut last_reboot: Option<Instant> = None;
+    let out_of_date_debounce = Duration::from_secs(server_settings.out_of_date_debounce_sec);
+    let update_debounce = match once {
+        true => Duration::ZERO,
+        false => Duration::from_secs(server_settings.update_debounce_sec),
+    };
+    let mut pending = PendingUpdates::default();
+
+    let mut events = match once {
+        true => None,
+        false => server_settings
+            .zomboid_dir
+            .as_ref()
+            .map(|zomboid_dir| LogTailer::new(zomboid_dir, false).spawn(Duration::from_secs(1))),
+    };
+    let check_interval = Duration::from_secs(server_settings.check_interval_sec);
+
+    loop {
+        let reasons = check_for_updates(config, server_settings).await;
+
+        if reasons.is_empty() {
+            info!("Server and workshop items are up to date");
+        } else if pending.is_empty() && reasons == last_handled {
+            warn!("Server is still out of date after the last reboot:");
+            for reason in &reasons {
+                warn!("  {}", reason);
+            }
+        } else {
+            if reasons != last_handled {
+                let message = "The server reboots for these updates";
+                notify::notify(config, Notification::new(NotifyEvent::Update, "Update found", message).details(&reasons)).await;
+            }
+            for reason in &reasons {
+                pending.add(reason.clone());
+            }
+            last_handled = reasons;
+        }
+
+        let next_check = tokio::time::sleep(check_interval);
+        tokio::pin!(next_check);
+        loop {
+            if !pending.is_empty() && pending.is_due(update_debounce) && maintenance_allows(server_settings, &mut pending, Utc::now()) {
+                for reason in &pending.reasons {
+                    info!("Rebooting for {}", reason);
+                }
+                let notice = reboot_notice(&pending.reasons);
+                if reboot_server(config, server_settings, &notice, &mut events).await.is_err() {
+                    // keep the updates around so the next check doesn't see them as handled
+                    last_handled.clear();
+                }
+                last_reboot = Some(Instant::now());
+                pending.clear();
+            }
+
+            if once {
+                return;
+            }
+
+            // wake up every minute while updates are waiting for the debounce period or a window
+            let tick = match pending.is_empty() {
+                true => check_interval,
+                false => Duration::from_secs(60),
+            };
+            let event = tokio::select! {
+                _ = &mut next_check => break,
+                _ = tokio::time::sleep(tick) => continue,
+                event = next_event(&mut events) => event,
+            };
+            let event = match event {
+                Some(event) => event,
+                None => {
+                    events = None;
+                    continue;
+                }
+            };
+            log_event(&event);
+
+            if let ServerEvent::WorkshopOutOfDate { workshop_id, message } = event {
+                if last_reboot.is_some_and(|last_reboot| last_reboot.elapsed() < out_of_date_debounce) {
+                    debug!("Ignoring outdated workshop item report, the server was rebooted recently");
+                    continue;
+                }
+                pending.add(UpdateReason::ServerReported { workshop_id, message });
+            }
+        }
+    }
+}
+
+/// Waits forever when there is no log tailer, so `select!` only wakes up for the timer.
+async fn next_event(events: &mut Option<mpsc::UnboundedReceiver<ServerEvent>>) -> Option<ServerEvent> {
+    match events {
+        Some(events) => events.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MaintenanceSettings, MaintenanceWindow};
+    use crate::workshop_state::StaleReason;
+
+    fn reboot_settings(reboot_policy: RebootPolicy, marker: &std::path::Path) -> ServerSettings {
+        ServerSettings {
+            reboot_command: format!("echo rebooted >> {}", marker.display()),
+            reboot_delay_sec: 600,
+            rcon_messages: true,
+            reboot_policy,
+            ..Default::default()
+        }
+    }
+
+    fn reboots(marker: &std::path::Path) -> usize {
+        std::fs::read_to_string(marker).unwrap_or_default().lines().count()
+    }
+
+    #[tokio::test]
+    async fn empty_server_test() {
+        let marker = std::env::temp_dir().join(format!("zso-empty-reboot-{}", std::process::id()));
+        let config = ZSOConfig {
+            rcon: Some(rcon::tests::fake_server(0).await),
+            ..Default::default()
+        };
+        let server_settings = reboot_settings(RebootPolicy::Countdown, &marker);
+
+        // no countdown, it would take 600s
+        let rebooted = tokio::time::timeout(
+            Duration::from_secs(10),
+            reboot_server(&config, &server_settings, &RebootNotice::default(), &mut None),
+        )
+        .await;
+        let reboot_count = reboots(&marker);
+        let _ = std::fs::remove_file(&marker);
+
+        assert_eq!(Ok(Ok(())), rebooted);
+        assert_eq!(1, reboot_count);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_empty_timeout_test() {
+        let marker = std::env::temp_dir().join(format!("zso-wait-reboot-{}", std::process::id()));
+        let config = ZSOConfig::default();
+        let server_settings = reboot_settings(RebootPolicy::WaitForEmpty { max_delay_sec: 90 }, &marker);
+
+        // without RCON the server never looks empty, so the wait runs out and the countdown follows
+        let started = tokio::time::Instant::now();
+        let rebooted = reboot_server(&config, &server_settings, &RebootNotice::default(), &mut None).await;
+        let waited = started.elapsed();
+        let reboot_count = reboots(&marker);
+        let _ = std::fs::remove_file(&marker);
+
+        assert_eq!(Ok(()), rebooted);
+        assert_eq!(1, reboot_count);
+        assert!(waited >= Duration::from_secs(90 + 600), "rebooted after {:?}", waited);
+    }
+
+    #[test]
+    fn vote_threshold_test() {
+        assert!(vote_passed(2, Some(3)));
+        assert!(!vote_passed(2, Some(4)));
+        assert!(vote_passed(1, Some(1)));
+        assert!(!vote_passed(0, Some(0)));
+        // RCON didn't answer, one voter can't decide for everybody
+        assert!(!vote_passed(1, None));
+    }
+
+    fn stale(workshop_id: u64, latest: u64) -> StaleItem {
+        StaleItem {
+            workshop_id,
+            title: format!("Item {workshop_id}"),
+            reason: StaleReason::Outdated { installed: 1, latest },
+        }
+    }
+
+    #[test]
+    fn pending_updates_merge_test() {
+        let mut pending = PendingUpdates::default();
+        pending.add(UpdateReason::Mods(vec![stale(1, 10)]));
+        pending.add(UpdateReason::GameBuild {
+            branch: "public".to_owned(),
+            installed: 1,
+            latest: 2,
+        });
+        pending.add(UpdateReason::Mods(vec![stale(1, 11), stale(2, 20)]));
+        pending.add(UpdateReason::GameBuild {
+            branch: "public".to_owned(),
+            installed: 1,
+            latest: 3,
+        });
+        let reported = UpdateReason::ServerReported {
+            workshop_id: Some(3),
+            message: "Workshop item 3 is out of date".to_owned(),
+        };
+        pending.add(reported.clone());
+        pending.add(reported.clone());
+
+        assert_eq!(
+            vec![
+                UpdateReason::Mods(vec![stale(1, 11), stale(2, 20)]),
+                UpdateReason::GameBuild {
+                    branch: "public".to_owned(),
+                    installed: 1,
+                    latest: 3,
+                },
+                reported,
+            ],
+            pending.reasons
+        );
+        assert_eq!(vec!["mod_update", "game_update", "outdated_mod"], reboot_notice(&pending.reasons).reasons);
+    }
+
+    #[test]
+    fn pending_updates_debounce_test() {
+        let mut pending = PendingUpdates::default();
+        assert!(!pending.is_due(Duration::ZERO));
+
+        pending.add(UpdateReason::Mods(vec![stale(1, 10)]));
+        assert!(pending.is_due(Duration::ZERO));
+        assert!(!pending.is_due(Duration::from_secs(300)));
+
+        // later updates don't push the reboot back
+        let since = pending.since;
+        pending.add(UpdateReason::Mods(vec![stale(2, 20)]));
+        assert_eq!(since, pending.since);
+
+        pending.clear();
+        assert!(pending.is_empty());
+        assert!(!pending.is_due(Duration::ZERO));
+    }
+
+    #[test]
+    fn pending_updates_held_test() {
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+        let server_settings = ServerSettings {
+            maintenance: Some(MaintenanceSettings {
+                timezone: "UTC".to_owned(),
+                windows: vec![MaintenanceWindow {
+                    days: vec![],
+                    start: "04:00".to_owned(),
+                    end: "05:00".to_owned(),
+                }],
+                emergency_override: true,
+            }),
+            ..Default::default()
+        };
+
+        let mut pending = PendingUpdates::default();
+        pending.add(UpdateReason::Mods(vec![stale(1, 10)]));
+        assert!(!maintenance_allows(&server_settings, &mut pending, at("2026-10-19T12:00:00Z")));
+        assert!(pending.held);
+        assert!(maintenance_allows(&server_settings, &mut pending, at("2026-10-20T04:30:00Z")));
+
+        // players can't join until the server has the new build
+        pending.add(UpdateReason::GameBuild {
+            branch: "public".to_owned(),
+            installed: 1,
+            latest: 2,
+        });
+        assert!(maintenance_allows(&server_settings, &mut pending, at("2026-10-19T12:00:00Z")));
+        assert!(maintenance_allows(&ServerSettings::default(), &mut pending, at("2026-10-19T12:00:00Z")));
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use std::sync::LazyLock;
+
+use log::debug;
+use regex::Regex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::config::RconSettings;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+const RCON_TIMEOUT: Duration = Duration::from_secs(10);
+
+static PLAYERS_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"Players connected \((?P<count>\d+)\)").unwrap());
+
+/// Source RCON client, the protocol the PZ server speaks on `RCONPort`.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    pub async fn connect(settings: &RconSettings) -> Result<Self, String> {
+        let address = format!("{}:{}", settings.host, settings.port);
+        let stream = match tokio::time::timeout(RCON_TIMEOUT, TcpStream::connect(&address)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => return Err(format!("failed to connect to RCON at {} - {}", address, e)),
+            Err(_) => return Err(format!("timed out connecting to RCON at {}", address)),
+        };
+
+        let mut client = Self { stream, next_id: 1 };
+        let auth_id = client.send(SERVERDATA_AUTH, &settings.password).await?;
+
+        loop {
+            let (id, packet_type, _) = client.read().await?;
+            if packet_type != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+            return match id == auth_id {
+                true => Ok(client),
+                false => Err("RCON authentication failed - check the password".to_owned()),
+            };
+        }
+    }
+
+    pub async fn exec(&mut self, command: &str) -> Result<String, String> {
+        debug!("RCON: {}", command);
+        let command_id = self.send(SERVERDATA_EXECCOMMAND, command).await?;
+
+        loop {
+            let (id, packet_type, body) = self.read().await?;
+            if id == command_id && packet_type == SERVERDATA_RESPONSE_VALUE {
+                return Ok(body);
+            }
+        }
+    }
+
+    pub async fn broadcast(&mut self, message: &str) -> Result<(), String> {
+        // servermsg takes a single quoted argument
+        let message = message.replace('"', "'");
+        self.exec(&format!("servermsg \"{}\"", message)).await.map(|_| ())
+    }
+
+    async fn send(&mut self, packet_type: i32, body: &str) -> Result<i32, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut packet: Vec<u8> = vec![];
+        packet.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&packet_type.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+
+        match self.stream.write_all(&packet).await {
+            Ok(_) => Ok(id),
+            Err(e) => Err(format!("failed to send RCON packet - {}", e)),
+        }
+    }
+
+    async fn read(&mut self) -> Result<(i32, i32, String), String> {
+        let read = async {
+            let size = self.stream.read_i32_le().await?;
+            let id = self.stream.read_i32_le().await?;
+            let packet_type = self.stream.read_i32_le().await?;
+            let mut body = vec![0; (size - 8).max(0) as usize];
+            self.stream.read_exact(&mut body).await?;
+            Ok::<_, std::io::Error>((id, packet_type, body))
+        };
+
+        match tokio::time::timeout(RCON_TIMEOUT, read).await {
+            Ok(Ok((id, packet_type, body))) => {
+                let body = String::from_utf8_lossy(&body).trim_end_matches('\0').to_owned();
+                Ok((id, packet_type, body))
+            }
+            Ok(Err(e)) => Err(format!("failed to read RCON response - {}", e)),
+            Err(_) => Err("timed out waiting for RCON response".to_owned()),
+        }
+    }
+}
+
+/// Count from the `players` reply, `Players connected (2): \n-Kate\n-Bob`.
+pub fn parse_player_count(reply: &str) -> Option<u32> {
+    PLAYERS_RE
+        .captures(reply)
+        .and_then(|captures| captures["count"].parse().ok())
+}
+
+pub async fn players_online(settings: &RconSettings) -> Result<u32, String> {
+    let mut client = RconClient::connect(settings).await?;
+    let reply = client.exec("players").await?;
+    parse_player_count(&reply).ok_or(format!("unexpected players reply: {reply}"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    async fn read_packet(stream: &mut TcpStream) -> (i32, i32, String) {
+        let size = stream.read_i32_le().await.unwrap();
+        let id = stream.read_i32_le().await.unwrap();
+        let packet_type = stream.read_i32_le().await.unwrap();
+        let mut body = vec![0; (size - 8) as usize];
+        stream.read_exact(&mut body).await.unwrap();
+        (id, packet_type, String::from_utf8_lossy(&body).trim_end_matches('\0').to_owned())
+    }
+
+    async fn write_packet(stream: &mut TcpStream, id: i32, packet_type: i32, body: &str) {
+        let mut packet: Vec<u8> = vec![];
+        packet.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+        packet.extend_from_slice(&id.to_le_bytes());
+        packet.extend_from_slice(&packet_type.to_le_bytes());
+        packet.extend_from_slice(body.as_bytes());
+        packet.extend_from_slice(&[0, 0]);
+        stream.write_all(&packet).await.unwrap();
+    }
+
+    /// A server that takes any password and reports `players` online, every other command gets an empty reply.
+    pub(crate) async fn fake_server(players: u32) -> RconSettings {
+        fake_server_on(TcpListener::bind("127.0.0.1:0").await.unwrap(), players)
+    }
+
+    /// `fake_server` on a listener bound by the test.
+    pub(crate) fn fake_server_on(listener: TcpListener, players: u32) -> RconSettings {
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let (id, _, _) = read_packet(&mut stream).await;
+                    write_packet(&mut stream, id, SERVERDATA_AUTH_RESPONSE, "").await;
+                    while let Ok(size) = stream.read_i32_le().await {
+                        let id = stream.read_i32_le().await.unwrap();
+                        let mut rest = vec![0; (size - 4) as usize];
+                        stream.read_exact(&mut rest).await.unwrap();
+                        let command = String::from_utf8_lossy(&rest[4..]).trim_end_matches('\0').to_owned();
+                        let reply = match command.as_str() {
+                            "players" => format!("Players connected ({players}):"),
+                            _ => String::new(),
+                        };
+                        write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, &reply).await;
+                    }
+                });
+            }
+        });
+
+        RconSettings {
+            host: "127.0.0.1".to_owned(),
+            port: port.to_string(),
+            password: "secret".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn rcon_exec_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (id, packet_type, password) = read_packet(&mut stream).await;
+            assert_eq!((SERVERDATA_AUTH, "secret"), (packet_type, password.as_str()));
+            write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, "").await;
+            write_packet(&mut stream, id, SERVERDATA_AUTH_RESPONSE, "").await;
+
+            let (id, _, command) = read_packet(&mut stream).await;
+            assert_eq!("players", command);
+            write_packet(&mut stream, id, SERVERDATA_RESPONSE_VALUE, "Players connected (0):").await;
+        });
+
+        let settings = RconSettings {
+            host: "127.0.0.1".to_owned(),
+            port: port.to_string(),
+            password: "secret".to_owned(),
+            ..Default::default()
+        };
+        let mut client = RconClient::connect(&settings).await.unwrap();
+        assert_eq!(Some(0), parse_player_count(&client.exec("players").await.unwrap()));
+        server.await.unwrap();
+        assert_eq!(Some(2), parse_player_count("Players connected (2): \n-Kate\n-Bob\n"));
+    }
+}
@@ -0,0 +1,210 @@
+use log::warn;
+
+use crate::config::{CountdownStep, LocalizedMessage, RconMessagesSettings};
+use crate::template::{Template, TemplateContext, TemplateValue};
+
+/// What players are told about a reboot.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct RebootNotice {
+    /// Language independent keys like `mod_update`, worded by `rcon.messages.reasons`.
+    pub reasons: Vec<String>,
+    /// Titles of the updated mods.
+    pub mods: Vec<String>,
+}
+
+impl RebootNotice {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reasons: vec![reason.into()],
+            mods: vec![],
+        }
+    }
+}
+
+/// English wording of the reasons zso reboots for.
+fn default_reason(key: &str) -> &str {
+    match key {
+        "mod_update" => "mod updates",
+        "game_update" => "a game update",
+        "outdated_mod" => "an outdated mod",
+        "scheduled" => "a scheduled reboot",
+        "config_change" => "a config change",
+        _ => key,
+    }
+}
+
+/// Reasons worded for `language`, falling back to English.
+fn reason_texts<'a>(messages: &'a RconMessagesSettings, notice: &'a RebootNotice, language: Option<&str>) -> Vec<&'a str> {
+    notice
+        .reasons
+        .iter()
+        .map(|key| {
+            messages
+                .reasons
+                .get(key)
+                .and_then(|reason| reason.translation(language))
+                .unwrap_or_else(|| default_reason(key))
+        })
+        .collect()
+}
+
+/// The configured steps longest first, or the fixed 15, 5 and 1 minute messages. Steps without a message are kept,
+/// they still set the pace of the countdown.
+pub fn countdown_steps(messages: &RconMessagesSettings) -> Vec<CountdownStep> {
+    let mut steps = match messages.countdown.is_empty() {
+        true => [(15, &messages.reboot_15m), (5, &messages.reboot_5m), (1, &messages.reboot_1m)]
+            .into_iter()
+            .map(|(minutes, message)| CountdownStep {
+                minutes,
+                message: LocalizedMessage::Text(message.clone()),
+            })
+            .collect(),
+        false => messages.countdown.clone(),
+    };
+
+    steps.sort_by_key(|step| std::cmp::Reverse(step.minutes));
+    steps
+}
+
+impl LocalizedMessage {
+    /// Translations in broadcast order with their language, languages the message doesn't have are skipped.
+    pub fn variants<'a>(&'a self, languages: &'a [String]) -> Vec<(Option<&'a str>, &'a str)> {
+        match self {
+            LocalizedMessage::Text(text) => vec![(None, text.as_str())],
+            LocalizedMessage::Languages(translations) if languages.is_empty() => translations
+                .iter()
+                .map(|(language, text)| (Some(language.as_str()), text.as_str()))
+                .collect(),
+            LocalizedMessage::Languages(translations) => languages
+                .iter()
+                .filter_map(|language| Some((Some(language.as_str()), translations.get(language)?.as_str())))
+                .collect(),
+        }
+    }
+
+    /// The text for `language`, a plain text fits every language.
+    pub fn translation(&self, language: Option<&str>) -> Option<&str> {
+        match self {
+            LocalizedMessage::Text(text) => Some(text),
+            LocalizedMessage::Languages(translations) => translations.get(language?).map(String::as_str),
+        }
+    }
+}
+
+/// A message that isn't a valid template goes out as it is.
+pub fn render(message: &str, minutes: u64, notice: &RebootNotice, reasons: &[&str], downtime_min: u64) -> String {
+    let template = match Template::parse(message) {
+        Ok(template) => template,
+        Err(e) => {
+            warn!("Broadcasting \"{}\" unrendered - {}", message, e);
+            return message.to_owned();
+        }
+    };
+
+    let mut context = TemplateContext::new();
+    context.insert("minutes".to_owned(), TemplateValue::text(minutes));
+    context.insert("reason".to_owned(), TemplateValue::text(reasons.join(" and ")));
+    context.insert("reasons".to_owned(), TemplateValue::list(reasons));
+    context.insert("reason_keys".to_owned(), TemplateValue::list(&notice.reasons));
+    context.insert("mods".to_owned(), TemplateValue::list(&notice.mods));
+    context.insert("downtime".to_owned(), TemplateValue::text(downtime_min));
+    template.render(&context)
+}
+
+/// Every message for one countdown step, non-empty ones only.
+pub fn step_messages(messages: &RconMessagesSettings, step: &CountdownStep, notice: &RebootNotice) -> Vec<String> {
+    step.message
+        .variants(&messages.languages)
+        .into_iter()
+        .filter(|(_, message)| !message.is_empty())
+        .map(|(language, message)| {
+            let reasons = reason_texts(messages, notice, language);
+            render(message, step.minutes, notice, &reasons, messages.downtime_min)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn countdown_messages_test() {
+        let legacy: RconMessagesSettings = serde_yaml::from_str("reboot_15m: Reboot in 15\nreboot_1m: Reboot in 1\n").unwrap();
+        let steps = countdown_steps(&legacy);
+        assert_eq!(vec![15, 5, 1], steps.iter().map(|step| step.minutes).collect::<Vec<u64>>());
+        assert!(step_messages(&legacy, &steps[1], &RebootNotice::default()).is_empty());
+
+        let messages: RconMessagesSettings = serde_yaml::from_str(
+            "
+languages: [en, de]
+downtime_min: 3
+countdown:
+  - minutes: 2
+    message: 'Restart in {{ minutes }} min'
+  - minutes: 10
+    message:
+      de: 'Neustart in {{ minutes }} Minuten wegen {{ reason }}: {{ mods | join \", \" }}'
+      en: 'Restart in {{ minutes }} minutes for {{ reason }}: {{ mods | join \", \" }}, back in {{ downtime }}'
+      fr: 'Redemarrage dans {{ minutes }} minutes'
+",
+        )
+        .unwrap();
+        let notice = RebootNotice {
+            reasons: vec!["mod_update".to_owned()],
+            mods: vec!["Brita's Weapon Pack".to_owned(), "Arsenal".to_owned()],
+        };
+        let steps = countdown_steps(&messages);
+
+        assert_eq!(vec![10, 2], steps.iter().map(|step| step.minutes).collect::<Vec<u64>>());
+        assert_eq!(
+            vec![
+                "Restart in 10 minutes for mod updates: Brita's Weapon Pack, Arsenal, back in 3",
+                "Neustart in 10 Minuten wegen mod updates: Brita's Weapon Pack, Arsenal",
+            ],
+            step_messages(&messages, &steps[0], &notice)
+        );
+        assert_eq!(vec!["Restart in 2 min"], step_messages(&messages, &steps[1], &notice));
+    }
+
+    #[test]
+    fn reason_translation_test() {
+        let messages: RconMessagesSettings = serde_yaml::from_str(
+            "
+languages: [en, de]
+reasons:
+  mod_update:
+    de: Mod-Updates
+  scheduled: the weekly reboot
+countdown:
+  - minutes: 5
+    message:
+      de: 'Neustart in {{ minutes }} Minuten wegen {{ reasons | join \" und \" }}'
+      en: 'Restart in {{ minutes }} minutes for {{ reason }} ({{ reason_keys | join \",\" }})'
+",
+        )
+        .unwrap();
+        let steps = countdown_steps(&messages);
+        let notice = RebootNotice {
+            reasons: vec!["mod_update".to_owned(), "game_update".to_owned()],
+            mods: vec![],
+        };
+
+        // reasons without a translation fall back to English
+        assert_eq!(
+            vec![
+                "Restart in 5 minutes for mod updates and a game update (mod_update,game_update)",
+                "Neustart in 5 Minuten wegen Mod-Updates und a game update",
+            ],
+            step_messages(&messages, &steps[0], &notice)
+        );
+        // a plain text reason is used for every language
+        assert_eq!(
+            vec![
+                "Restart in 5 minutes for the weekly reboot (scheduled)",
+                "Neustart in 5 Minuten wegen the weekly reboot",
+            ],
+            step_messages(&messages, &steps[0], &RebootNotice::new("scheduled"))
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::DateTime;
+
+use crate::steam_api_client::ModData;
+
+const WORKSHOP_URL: &str = "https://steamcommunity.com/sharedfiles/filedetails/?id=";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+pub fn human_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} {}", bytes, units[0]),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+pub fn format_date(timestamp: u64) -> String {
+    match DateTime::from_timestamp(timestamp as i64, 0) {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => "-".to_owned(),
+    }
+}
+
+fn workshop_link(workshop_id: u64) -> String {
+    format!("{WORKSHOP_URL}{workshop_id}")
+}
+
+/// Items grouped by the collection that lists them, in config order.
+/// An item listed by several collections shows up in each of them.
+fn group_by_collection<'a>(collections: &[u64], mods_data: &'a [ModData]) -> Vec<(u64, Vec<&'a ModData>)> {
+    collections
+        .iter()
+        .map(|collection_id| {
+            let items = mods_data
+                .iter()
+                .filter(|mod_data| mod_data.collections.contains(collection_id))
+                .collect::<Vec<&ModData>>();
+            (*collection_id, items)
+        })
+        .filter(|(_, items)| !items.is_empty())
+        .collect()
+}
+
+/// Unique items and their summed download size, an item can be listed by several collections.
+fn totals(mods_data: &[ModData]) -> (usize, u64) {
+    let mut seen: Vec<u64> = vec![];
+    let mut total = 0;
+
+    for mod_data in mods_data {
+        if !seen.contains(&mod_data.mod_id) {
+            seen.push(mod_data.mod_id);
+            total += mod_data.file_size;
+        }
+    }
+
+    (seen.len(), total)
+}
+
+fn or_dash(list: &[String]) -> String {
+    match list.is_empty() {
+        true => "-".to_owned(),
+        false => list.join(", "),
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('[', "\\[").replace(']', "\\]")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_markdown(collections: &[u64], mods_data: &[ModData]) -> String {
+    let (item_count, size) = totals(mods_data);
+    let mut markdown = String::from("# Server mods\n\n");
+    markdown += &format!(
+        "{} workshop items, total download size **{}**.\n",
+        item_count,
+        human_size(size)
+    );
+
+    for (collection_id, items) in group_by_collection(collections, mods_data) {
+        markdown += &format!(
+            "\n## [Collection {}]({})\n\n",
+            collection_id,
+            workshop_link(collection_id)
+        );
+        markdown += "| Preview | Title | Mod IDs | Maps | Size | Updated |\n";
+        markdown += "|---|---|---|---|---|---|\n";
+
+        for item in items {
+            let preview = match item.preview_url.is_empty() {
+                true => String::new(),
+                false => format!("<img src=\"{}\" width=\"64\">", escape_html(&item.preview_url)),
+            };
+            markdown += &format!(
+                "| {} | [{}]({}) | {} | {} | {} | {} |\n",
+                preview,
+                escape_markdown(&item.title),
+                workshop_link(item.mod_id),
+                escape_markdown(&or_dash(&item.mod_name)),
+                escape_markdown(&or_dash(&item.map_name)),
+                human_size(item.file_size),
+                format_date(item.last_updated)
+            );
+        }
+    }
+
+    markdown
+}
+
+pub fn render_html(collections: &[u64], mods_data: &[ModData]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Server mods</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }\n\
+         th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: middle; }\n\
+         img { width: 64px; }\n\
+         </style>\n</head>\n<body>\n<h1>Server mods</h1>\n",
+    );
+    let (item_count, size) = totals(mods_data);
+    html += &format!(
+        "<p>{} workshop items, total download size <strong>{}</strong>.</p>\n",
+        item_count,
+        human_size(size)
+    );
+
+    for (collection_id, items) in group_by_collection(collections, mods_data) {
+        html += &format!(
+            "<h2><a href=\"{}\">Collection {}</a></h2>\n<table>\n",
+            workshop_link(collection_id),
+            collection_id
+        );
+        html += "<tr><th>Preview</th><th>Title</th><th>Mod IDs</th><th>Maps</th><th>Size</th><th>Updated</th></tr>\n";
+
+        for item in items {
+            let preview = match item.preview_url.is_empty() {
+                true => String::new(),
+                false => format!("<img src=\"{}\" alt=\"\">", escape_html(&item.preview_url)),
+            };
+            html += &format!(
+                "<tr><td>{}</td><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                preview,
+                workshop_link(item.mod_id),
+                escape_html(&item.title),
+                escape_html(&or_dash(&item.mod_name)),
+                escape_html(&or_dash(&item.map_name)),
+                human_size(item.file_size),
+                format_date(item.last_updated)
+            );
+        }
+
+        html += "</table>\n";
+    }
+
+    html += "</body>\n</html>\n";
+    html
+}
+
+pub fn render_report(format: ReportFormat, collections: &[u64], mods_data: &[ModData]) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(collections, mods_data),
+        ReportFormat::Html => render_html(collections, mods_data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(mod_id: u64, title: &str, collections: &[u64], file_size: u64) -> ModData {
+        ModData {
+            mod_id,
+            title: title.to_owned(),
+            mod_name: vec![title.replace(' ', "")],
+            collections: collections.to_vec(),
+            file_size,
+            last_updated: 1700000000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn totals_test() {
+        let mods_data = [item(1, "A", &[100], 1024), item(2, "B", &[100], 2048), item(1, "A", &[100], 1024)];
+
+        assert_eq!((2, 3072), totals(&mods_data));
+        assert_eq!((0, 0), totals(&[]));
+        assert_eq!("512 B", human_size(512));
+        assert_eq!("3.0 KB", human_size(3072));
+        assert_eq!("1.5 GB", human_size(1536 * 1024 * 1024));
+    }
+
+    #[test]
+    fn render_markdown_test() {
+        let mods_data = [
+            item(1, "Brita's [Weapon] Pack", &[100, 200], 1024 * 1024),
+            item(2, "Arsenal | Gunfighter", &[200], 2048),
+        ];
+
+        let markdown = render_markdown(&[100, 200, 300], &mods_data);
+
+        assert_eq!(
+            "# Server mods\n\n\
+             2 workshop items, total download size **1.0 MB**.\n\
+             \n## [Collection 100](https://steamcommunity.com/sharedfiles/filedetails/?id=100)\n\n\
+             | Preview | Title | Mod IDs | Maps | Size | Updated |\n\
+             |---|---|---|---|---|---|\n\
+             |  | [Brita's \\[Weapon\\] Pack](https://steamcommunity.com/sharedfiles/filedetails/?id=1) | Brita's\\[Weapon\\]Pack | - | 1.0 MB | 2023-11-14 |\n\
+             \n## [Collection 200](https://steamcommunity.com/sharedfiles/filedetails/?id=200)\n\n\
+             | Preview | Title | Mod IDs | Maps | Size | Updated |\n\
+             |---|---|---|---|---|---|\n\
+             |  | [Brita's \\[Weapon\\] Pack](https://steamcommunity.com/sharedfiles/filedetails/?id=1) | Brita's\\[Weapon\\]Pack | - | 1.0 MB | 2023-11-14 |\n\
+             |  | [Arsenal \\| Gunfighter](https://steamcommunity.com/sharedfiles/filedetails/?id=2) | Arsenal\\|Gunfighter | - | 2.0 KB | 2023-11-14 |\n",
+            markdown
+        );
+    }
+}
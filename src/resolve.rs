@@ -0,0 +1,315 @@
+use log::info;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::config::ConfigWorkshopSettings;
+use crate::mod_conflicts::{Conflict, ConflictKind};
+use crate::steam_api_client::{ModData, SteamApiClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Json,
+    Yaml,
+    Env,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemStatus {
+    Included,
+    Excluded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedItem {
+    pub workshop_id: u64,
+    pub title: String,
+    pub status: ItemStatus,
+    pub reason: String,
+    pub collections: Vec<u64>,
+    pub mod_ids: Vec<String>,
+    pub excluded_mod_ids: Vec<String>,
+    pub maps: Vec<String>,
+    pub excluded_maps: Vec<String>,
+    pub last_updated: u64,
+}
+
+/// Everything a resolve run produced, in the shape printed by `--output`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedModel {
+    pub collections: Vec<u64>,
+    pub items: Vec<ResolvedItem>,
+    pub workshop_items: String,
+    pub mods: String,
+    pub map: Option<String>,
+}
+
+/// Lists the collections and resolves every item in them.
+/// Returns the per-collection listing next to the mods data.
+pub(crate) async fn fetch_mods_data(
+    steam_api_client: &SteamApiClient,
+    collections: &[u64],
+) -> (Vec<(u64, Vec<u64>)>, Vec<ModData>) {
+    let collections_children = steam_api_client
+        .get_collections_children(collections.to_vec())
+        .await;
+    let full_mod_list = collections_children
+        .iter()
+        .flat_map(|(_, children)| children.clone())
+        .collect::<Vec<u64>>();
+
+    info!("Total mods in collections: {}", full_mod_list.len());
+
+    if full_mod_list.is_empty() {
+        return (collections_children, vec![]);
+    }
+
+    let mut mods_data = steam_api_client.resolve_mods_data(full_mod_list).await;
+    assign_collections(&mut mods_data, &collections_children);
+
+    info!("Total parsed mods: {}", mods_data.len());
+
+    (collections_children, mods_data)
+}
+
+/// Fills `ModData::collections` from the collection listing.
+pub(crate) fn assign_collections(mods_data: &mut [ModData], collections_children: &[(u64, Vec<u64>)]) {
+    for mod_data in mods_data.iter_mut() {
+        for (collection_id, children) in collections_children {
+            if children.contains(&mod_data.mod_id) && !mod_data.collections.contains(collection_id) {
+                mod_data.collections.push(*collection_id);
+            }
+        }
+    }
+}
+
+fn describe_source(mod_data: &ModData) -> String {
+    match mod_data.collections.as_slice() {
+        [] => "listed in a collection".to_owned(),
+        collections => format!(
+            "listed in collection {}",
+            collections
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Builds the model from the mods data before conflict resolution was applied,
+/// so items dropped by a conflict are reported too.
+pub(crate) fn build_model(
+    collections: &[u64],
+    mods_data: &[ModData],
+    workshop_settings: &ConfigWorkshopSettings,
+    conflicts: &[Conflict],
+    strings: (&str, &str, Option<&str>),
+) -> ResolvedModel {
+    let include = &workshop_settings.include;
+    let exclude = &workshop_settings.exclude;
+    let mut items: Vec<ResolvedItem> = vec![];
+
+    for mod_data in mods_data {
+        let lost_conflicts = conflicts
+            .iter()
+            .filter(|conflict| conflict.losers().contains(&mod_data.mod_id))
+            .collect::<Vec<&Conflict>>();
+        let lost = |kind: ConflictKind, name: &String| {
+            lost_conflicts
+                .iter()
+                .any(|conflict| conflict.kind == kind && conflict.name == *name)
+        };
+
+        let (mod_ids, excluded_mod_ids): (Vec<String>, Vec<String>) = mod_data
+            .mod_name
+            .iter()
+            .cloned()
+            .partition(|mod_name| !exclude.mods.contains(mod_name) && !lost(ConflictKind::ModId, mod_name));
+        let (maps, excluded_maps): (Vec<String>, Vec<String>) = mod_data
+            .map_name
+            .iter()
+            .cloned()
+            .partition(|map_name| !exclude.maps.contains(map_name) && !lost(ConflictKind::MapFolder, map_name));
+
+        let lost_reasons = lost_conflicts
+            .iter()
+            .map(|conflict| {
+                format!(
+                    "lost the {} conflict for {} to {}",
+                    conflict.kind,
+                    conflict.name,
+                    conflict.winner.unwrap_or_default()
+                )
+            })
+            .collect::<Vec<String>>();
+        let nothing_left =
+            mod_ids.is_empty() && maps.is_empty() && !(excluded_mod_ids.is_empty() && excluded_maps.is_empty());
+
+        let (status, reason) = match nothing_left {
+            _ if exclude.workshop_items.contains(&mod_data.mod_id) => {
+                (ItemStatus::Excluded, "excluded by workshop_settings.exclude".to_owned())
+            }
+            // the item may still download, but nothing of it gets loaded
+            true if lost_reasons.is_empty() => (
+                ItemStatus::Excluded,
+                "all its mods and maps are excluded by workshop_settings.exclude".to_owned(),
+            ),
+            true => (ItemStatus::Excluded, lost_reasons.join(", ")),
+            false => (
+                ItemStatus::Included,
+                std::iter::once(describe_source(mod_data))
+                    .chain(lost_reasons)
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            ),
+        };
+
+        items.push(ResolvedItem {
+            workshop_id: mod_data.mod_id,
+            title: mod_data.title.clone(),
+            status,
+            reason,
+            collections: mod_data.collections.clone(),
+            mod_ids,
+            excluded_mod_ids,
+            maps,
+            excluded_maps,
+            last_updated: mod_data.last_updated,
+        });
+    }
+
+    for workshop_id in &include.workshop_items {
+        if items.iter().any(|item| item.workshop_id == *workshop_id) {
+            continue;
+        }
+        items.push(ResolvedItem {
+            workshop_id: *workshop_id,
+            title: String::new(),
+            status: ItemStatus::Included,
+            reason: "added by workshop_settings.include".to_owned(),
+            collections: vec![],
+            mod_ids: vec![],
+            excluded_mod_ids: vec![],
+            maps: vec![],
+            excluded_maps: vec![],
+            last_updated: 0,
+        });
+    }
+
+    let (workshop_items, mods, map) = strings;
+
+    ResolvedModel {
+        collections: collections.to_vec(),
+        items,
+        workshop_items: workshop_items.to_owned(),
+        mods: mods.to_owned(),
+        map: map.map(str::to_owned),
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub(crate) fn render_model(model: &ResolvedModel, format: OutputFormat) -> Result<String, String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string_pretty(model).map_err(|e| e.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(model).map_err(|e| e.to_string()),
+        OutputFormat::Env => {
+            let mut env = String::new();
+            for item in &model.items {
+                env += &format!(
+                    "# {} {}: {:?}, {}\n",
+                    item.workshop_id, item.title, item.status, item.reason
+                );
+            }
+            env += &format!(
+                "WORKSHOP_ITEMS={}\nMODS={}\n",
+                shell_quote(&model.workshop_items),
+                shell_quote(&model.mods)
+            );
+            if let Some(map) = &model.map {
+                env += &format!("MAP={}\n", shell_quote(map));
+            }
+            Ok(env)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(mod_id: u64, mod_name: &[&str], map_name: &[&str]) -> ModData {
+        ModData {
+            mod_id,
+            mod_name: mod_name.iter().map(|name| name.to_string()).collect(),
+            map_name: map_name.iter().map(|name| name.to_string()).collect(),
+            title: format!("Item {mod_id}"),
+            collections: vec![100],
+            ..Default::default()
+        }
+    }
+
+    fn model() -> ResolvedModel {
+        let mut workshop_settings = ConfigWorkshopSettings::default();
+        workshop_settings.include.workshop_items = vec![5];
+        workshop_settings.exclude.workshop_items = vec![2];
+        workshop_settings.exclude.mods = vec!["Arsenal".to_owned()];
+        let conflicts = vec![Conflict {
+            kind: ConflictKind::ModId,
+            name: "Hydrocraft".to_owned(),
+            workshop_items: vec![1, 4],
+            winner: Some(1),
+        }];
+        let mods_data = vec![
+            item(1, &["Hydrocraft"], &[]),
+            item(2, &["Brita"], &[]),
+            item(3, &["Arsenal"], &[]),
+            item(4, &["Hydrocraft", "HydroExtras"], &[]),
+        ];
+
+        build_model(&[100], &mods_data, &workshop_settings, &conflicts, ("1;3;4;5;", "Hydrocraft;HydroExtras;", None))
+    }
+
+    #[test]
+    fn build_model_test() {
+        let model = model();
+        let statuses = model
+            .items
+            .iter()
+            .map(|item| (item.workshop_id, item.status, item.reason.as_str()))
+            .collect::<Vec<(u64, ItemStatus, &str)>>();
+
+        assert_eq!(
+            vec![
+                (1, ItemStatus::Included, "listed in collection 100"),
+                (2, ItemStatus::Excluded, "excluded by workshop_settings.exclude"),
+                (3, ItemStatus::Excluded, "all its mods and maps are excluded by workshop_settings.exclude"),
+                (4, ItemStatus::Included, "listed in collection 100, lost the Mod ID conflict for Hydrocraft to 1"),
+                (5, ItemStatus::Included, "added by workshop_settings.include"),
+            ],
+            statuses
+        );
+        assert_eq!(vec!["HydroExtras"], model.items[3].mod_ids);
+        assert_eq!(vec!["Hydrocraft"], model.items[3].excluded_mod_ids);
+    }
+
+    #[test]
+    fn render_model_test() {
+        let model = model();
+
+        let json: ResolvedModel = serde_json::from_str(&render_model(&model, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(model, json);
+        let yaml: ResolvedModel = serde_yaml::from_str(&render_model(&model, OutputFormat::Yaml).unwrap()).unwrap();
+        assert_eq!(model, yaml);
+
+        let env = render_model(&model, OutputFormat::Env).unwrap();
+        assert!(env.starts_with("# 1 Item 1: Included, listed in collection 100\n"));
+        assert!(env.ends_with("WORKSHOP_ITEMS='1;3;4;5;'\nMODS='Hydrocraft;HydroExtras;'\n"));
+        assert!(!env.contains("MAP="));
+        assert_eq!("'it'\\''s'", shell_quote("it's"));
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{error, warn};
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::steam_api_client::ModData;
+
+pub const SNAPSHOT_FILE_NAME: &str = "zso_last_run.json";
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotItem {
+    pub title: String,
+    pub mod_ids: Vec<String>,
+    pub maps: Vec<String>,
+}
+
+/// What a resolve run saw: collection listings and the parsed items.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSnapshot {
+    pub timestamp: u64,
+    pub collections: BTreeMap<u64, Vec<u64>>,
+    pub items: BTreeMap<u64, SnapshotItem>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportItem {
+    pub workshop_id: u64,
+    pub title: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectionChanges {
+    pub collection_id: u64,
+    pub added: Vec<ReportItem>,
+    pub removed: Vec<ReportItem>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModIdChange {
+    pub workshop_id: u64,
+    pub title: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewMap {
+    pub workshop_id: u64,
+    pub title: String,
+    pub map: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeReport {
+    pub previous_run: u64,
+    pub current_run: u64,
+    pub collections: Vec<CollectionChanges>,
+    pub mod_id_changes: Vec<ModIdChange>,
+    pub new_maps: Vec<NewMap>,
+}
+
+/// The snapshot sits next to the config, like `zso.lock`.
+pub fn snapshot_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(SNAPSHOT_FILE_NAME)
+}
+
+pub async fn load_snapshot(path: &Path) -> Option<RunSnapshot> {
+    let snapshot_data = match tokio::fs::read_to_string(path).await {
+        Ok(snapshot_data) => snapshot_data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Failed to open previous run {} - {}", path.display(), e);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&snapshot_data) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            warn!("Failed to parse previous run {} - {}", path.display(), e);
+            None
+        }
+    }
+}
+
+impl RunSnapshot {
+    pub fn new(collections_children: &[(u64, Vec<u64>)], mods_data: &[ModData]) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let collections = collections_children.iter().cloned().collect();
+        let items = mods_data
+            .iter()
+            .map(|mod_data| {
+                (
+                    mod_data.mod_id,
+                    SnapshotItem {
+                        title: mod_data.title.clone(),
+                        mod_ids: mod_data.mod_name.clone(),
+                        maps: mod_data.map_name.clone(),
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            timestamp,
+            collections,
+            items,
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<(), ()> {
+        let snapshot_data = match serde_json::to_string_pretty(self) {
+            Ok(snapshot_data) => snapshot_data,
+            Err(e) => {
+                error!("Failed to serialize run snapshot - {}", e);
+                return Err(());
+            }
+        };
+
+        match tokio::fs::write(path, snapshot_data.as_bytes()).await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to write run snapshot {} - {}", path.display(), e);
+                Err(())
+            }
+        }
+    }
+
+    fn report_item(&self, workshop_id: u64) -> ReportItem {
+        ReportItem {
+            workshop_id,
+            title: self
+                .items
+                .get(&workshop_id)
+                .map(|item| item.title.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl ChangeReport {
+    pub fn between(previous: &RunSnapshot, current: &RunSnapshot) -> Self {
+        let mut report = ChangeReport {
+            previous_run: previous.timestamp,
+            current_run: current.timestamp,
+            ..Default::default()
+        };
+
+        for (collection_id, children) in &current.collections {
+            let previous_children = match previous.collections.get(collection_id) {
+                Some(previous_children) => previous_children,
+                None => continue, // collection was just added to the config, everything would look new
+            };
+
+            let added = children
+                .iter()
+                .filter(|child| !previous_children.contains(child))
+                .map(|child| current.report_item(*child))
+                .collect::<Vec<ReportItem>>();
+            let removed = previous_children
+                .iter()
+                .filter(|child| !children.contains(child))
+                .map(|child| previous.report_item(*child))
+                .collect::<Vec<ReportItem>>();
+
+            if !added.is_empty() || !removed.is_empty() {
+                report.collections.push(CollectionChanges {
+                    collection_id: *collection_id,
+                    added,
+                    removed,
+                });
+            }
+        }
+
+        for (workshop_id, item) in &current.items {
+            let previous_item = match previous.items.get(workshop_id) {
+                Some(previous_item) => previous_item,
+                None => {
+                    for map in &item.maps {
+                        report.new_maps.push(NewMap {
+                            workshop_id: *workshop_id,
+                            title: item.title.clone(),
+                            map: map.clone(),
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            let added = item
+                .mod_ids
+                .iter()
+                .filter(|mod_id| !previous_item.mod_ids.contains(mod_id))
+                .cloned()
+                .collect::<Vec<String>>();
+            let removed = previous_item
+                .mod_ids
+                .iter()
+                .filter(|mod_id| !item.mod_ids.contains(mod_id))
+                .cloned()
+                .collect::<Vec<String>>();
+
+            if !added.is_empty() || !removed.is_empty() {
+                report.mod_id_changes.push(ModIdChange {
+                    workshop_id: *workshop_id,
+                    title: item.title.clone(),
+                    added,
+                    removed,
+                });
+            }
+
+            for map in &item.maps {
+                if !previous_item.maps.contains(map) {
+                    report.new_maps.push(NewMap {
+                        workshop_id: *workshop_id,
+                        title: item.title.clone(),
+                        map: map.clone(),
+                    });
+                }
+            }
+        }
+
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.collections.is_empty() && self.mod_id_changes.is_empty() && self.new_maps.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return "No collection changes since the previous run\n".to_owned();
+        }
+
+        let mut summary = String::new();
+
+        for collection in &self.collections {
+            summary += &format!("Collection {}:\n", collection.collection_id);
+            for item in &collection.added {
+                summary += &format!("  + {} {}\n", item.workshop_id, item.title);
+            }
+            for item in &collection.removed {
+                summary += &format!("  - {} {}\n", item.workshop_id, item.title);
+            }
+        }
+
+        for change in &self.mod_id_changes {
+            summary += &format!("Mod IDs of {} {} changed:", change.workshop_id, change.title);
+            if !change.added.is_empty() {
+                summary += &format!(" added {}", change.added.join(";"));
+            }
+            if !change.removed.is_empty() {
+                summary += &format!(" removed {}", change.removed.join(";"));
+            }
+            summary += "\n";
+        }
+
+        for new_map in &self.new_maps {
+            summary += &format!("New map folder {} in {} {}\n", new_map.map, new_map.workshop_id, new_map.title);
+        }
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(mod_id: u64, mod_name: &[&str], map_name: &[&str]) -> ModData {
+        ModData {
+            mod_id,
+            title: format!("Item {mod_id}"),
+            mod_name: mod_name.iter().map(|name| name.to_string()).collect(),
+            map_name: map_name.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn change_report_test() {
+        let previous = RunSnapshot::new(
+            &[(100, vec![1, 2, 3])],
+            &[item(1, &["Hydrocraft"], &[]), item(2, &["Brita"], &[]), item(3, &["Bedford"], &["Bedford Falls"])],
+        );
+        let current = RunSnapshot::new(
+            &[(100, vec![1, 3, 4]), (200, vec![5])],
+            &[
+                item(1, &["Hydrocraft2"], &[]),
+                item(3, &["Bedford"], &["Bedford Falls", "Bedford Falls North"]),
+                item(4, &["Raven"], &["Raven Creek"]),
+                item(5, &["Arsenal"], &[]),
+            ],
+        );
+
+        let report = ChangeReport::between(&previous, &current);
+
+        // collection 200 is new to the config, its items aren't reported as added
+        assert_eq!(
+            vec![CollectionChanges {
+                collection_id: 100,
+                added: vec![ReportItem {
+                    workshop_id: 4,
+                    title: "Item 4".to_owned()
+                }],
+                removed: vec![ReportItem {
+                    workshop_id: 2,
+                    title: "Item 2".to_owned()
+                }],
+            }],
+            report.collections
+        );
+        assert_eq!(
+            vec![ModIdChange {
+                workshop_id: 1,
+                title: "Item 1".to_owned(),
+                added: vec!["Hydrocraft2".to_owned()],
+                removed: vec!["Hydrocraft".to_owned()],
+            }],
+            report.mod_id_changes
+        );
+        assert_eq!(
+            vec![(3, "Bedford Falls North"), (4, "Raven Creek")],
+            report
+                .new_maps
+                .iter()
+                .map(|new_map| (new_map.workshop_id, new_map.map.as_str()))
+                .collect::<Vec<(u64, &str)>>()
+        );
+        assert!(report.summary().contains("Mod IDs of 1 Item 1 changed: added Hydrocraft2 removed Hydrocraft\n"));
+        assert!(ChangeReport::between(&current, &current).is_empty());
+    }
+}
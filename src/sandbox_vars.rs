@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::config::SandboxSettings;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SandboxValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl SandboxValue {
+    /// Parses a value typed on the command line, falling back to a string.
+    pub fn parse_cli(value: &str) -> Self {
+        if let Ok(value) = value.parse::<bool>() {
+            return SandboxValue::Bool(value);
+        }
+        if let Ok(value) = value.parse::<i64>() {
+            return SandboxValue::Integer(value);
+        }
+        if let Ok(value) = value.parse::<f64>() {
+            return SandboxValue::Float(value);
+        }
+        SandboxValue::String(value.to_owned())
+    }
+
+    pub fn to_lua(&self) -> String {
+        match self {
+            SandboxValue::Bool(value) => value.to_string(),
+            SandboxValue::Integer(value) => value.to_string(),
+            SandboxValue::Float(value) => format!("{value:?}"),
+            SandboxValue::String(value) => {
+                format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            SandboxValue::Integer(value) => Some(*value as f64),
+            SandboxValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// `1` and `1.0` are the same value to the game.
+    pub fn same_as(&self, other: &SandboxValue) -> bool {
+        match (self.as_f64(), other.as_f64()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self == other,
+        }
+    }
+}
+
+impl std::fmt::Display for SandboxValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_lua())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxEntry {
+    /// Dotted path below the root table, e.g. `ZombieLore.Speed`.
+    pub key: String,
+    pub value: SandboxValue,
+    span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SandboxTable {
+    key: String,
+    open: usize,
+    close: usize,
+    /// End of the last field and whether a `,`/`;` follows it.
+    last_field: Option<(usize, bool)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxChange {
+    pub key: String,
+    pub current: Option<SandboxValue>,
+    pub desired: SandboxValue,
+}
+
+/// `SandboxVars.lua` kept as source text. Edits splice values into the original text,
+/// so spacing, ordering and comments survive a round trip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxVars {
+    source: String,
+    entries: Vec<SandboxEntry>,
+    tables: Vec<SandboxTable>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Str(String),
+    Number(String),
+    Equals,
+    Open,
+    Close,
+    Separator,
+    LeftBracket,
+    RightBracket,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, Range<usize>)>, String> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let c = bytes[pos] as char;
+
+        if c.is_whitespace() {
+            pos += 1;
+        } else if source[pos..].starts_with("--[[") {
+            pos = match source[pos..].find("]]") {
+                Some(end) => pos + end + 2,
+                None => return Err(format!("unterminated block comment at {start}")),
+            };
+        } else if source[pos..].starts_with("--") {
+            pos = match source[pos..].find('\n') {
+                Some(end) => pos + end,
+                None => bytes.len(),
+            };
+        } else if c == '"' || c == '\'' {
+            let mut value = String::new();
+            pos += 1;
+            loop {
+                let next = match source[pos..].chars().next() {
+                    Some(next) => next,
+                    None => return Err(format!("unterminated string at {start}")),
+                };
+                pos += next.len_utf8();
+                match next {
+                    '\\' => {
+                        let escaped = match source[pos..].chars().next() {
+                            Some(escaped) => escaped,
+                            None => return Err(format!("unterminated string at {start}")),
+                        };
+                        pos += escaped.len_utf8();
+                        value.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            other => other,
+                        });
+                    }
+                    next if next == c => break,
+                    next => value.push(next),
+                }
+            }
+            tokens.push((Token::Str(value), start..pos));
+        } else if c.is_ascii_digit() || c == '-' || c == '.' {
+            pos += 1;
+            while pos < bytes.len()
+                && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'.' || (bytes[pos] == b'-' && bytes[pos - 1].eq_ignore_ascii_case(&b'e')))
+            {
+                pos += 1;
+            }
+            tokens.push((Token::Number(source[start..pos].to_owned()), start..pos));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            tokens.push((Token::Name(source[start..pos].to_owned()), start..pos));
+        } else {
+            let token = match c {
+                '=' => Token::Equals,
+                '{' => Token::Open,
+                '}' => Token::Close,
+                ',' | ';' => Token::Separator,
+                '[' => Token::LeftBracket,
+                ']' => Token::RightBracket,
+                other => return Err(format!("unexpected character '{other}' at {start}")),
+            };
+            pos += 1;
+            tokens.push((token, start..pos));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, Range<usize>)>,
+    pos: usize,
+    entries: Vec<SandboxEntry>,
+    tables: Vec<SandboxTable>,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<(Token, Range<usize>), String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token.ok_or_else(|| "unexpected end of file".to_owned())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<Range<usize>, String> {
+        let (token, span) = self.next()?;
+        if token != expected {
+            return Err(format!("expected {:?}, got {:?} at {}", expected, token, span.start));
+        }
+        Ok(span)
+    }
+
+    fn table(&mut self, key: &str) -> Result<(), String> {
+        let open = self.expect(Token::Open)?.start;
+        let mut last_field: Option<(usize, bool)> = None;
+
+        loop {
+            let name = match self.next()? {
+                (Token::Close, span) => {
+                    self.tables.push(SandboxTable {
+                        key: key.to_owned(),
+                        open,
+                        close: span.start,
+                        last_field,
+                    });
+                    return Ok(());
+                }
+                (Token::Name(name), _) => name,
+                (Token::LeftBracket, _) => match self.next()? {
+                    (Token::Str(name), _) | (Token::Number(name), _) => {
+                        self.expect(Token::RightBracket)?;
+                        name
+                    }
+                    (token, span) => return Err(format!("unsupported key {:?} at {}", token, span.start)),
+                },
+                (token, span) => return Err(format!("expected a key, got {:?} at {}", token, span.start)),
+            };
+            self.expect(Token::Equals)?;
+
+            let field_key = match key.is_empty() {
+                true => name,
+                false => format!("{key}.{name}"),
+            };
+
+            let field_end = match self.peek() {
+                Some(Token::Open) => {
+                    self.table(&field_key)?;
+                    self.tokens[self.pos - 1].1.end
+                }
+                _ => {
+                    let (token, span) = self.next()?;
+                    let value = match token {
+                        Token::Name(name) if name == "true" => SandboxValue::Bool(true),
+                        Token::Name(name) if name == "false" => SandboxValue::Bool(false),
+                        Token::Str(value) => SandboxValue::String(value),
+                        Token::Number(number) => match number.parse::<i64>() {
+                            Ok(value) => SandboxValue::Integer(value),
+                            Err(_) => match number.parse::<f64>() {
+                                Ok(value) => SandboxValue::Float(value),
+                                Err(_) => return Err(format!("bad number {} at {}", number, span.start)),
+                            },
+                        },
+                        token => return Err(format!("unsupported value {:?} at {}", token, span.start)),
+                    };
+                    let end = span.end;
+                    self.entries.push(SandboxEntry {
+                        key: field_key,
+                        value,
+                        span,
+                    });
+                    end
+                }
+            };
+
+            let separated = match self.peek() {
+                Some(Token::Separator) => {
+                    self.pos += 1;
+                    true
+                }
+                _ => false,
+            };
+            last_field = Some((field_end, separated));
+        }
+    }
+}
+
+impl SandboxVars {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            entries: vec![],
+            tables: vec![],
+        };
+
+        match parser.next()? {
+            (Token::Name(_), _) => {}
+            (token, span) => return Err(format!("expected the root table name, got {:?} at {}", token, span.start)),
+        }
+        parser.expect(Token::Equals)?;
+        parser.table("")?;
+
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("unexpected data after the root table at {}", parser.tokens[parser.pos].1.start));
+        }
+
+        Ok(Self {
+            source: source.to_owned(),
+            entries: parser.entries,
+            tables: parser.tables,
+        })
+    }
+
+    pub fn entries(&self) -> &[SandboxEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SandboxValue> {
+        self.entries.iter().find(|entry| entry.key == key).map(|entry| &entry.value)
+    }
+
+    /// Replaces the value in place or appends a new field to its parent table.
+    pub fn set(&mut self, key: &str, value: &SandboxValue) -> Result<(), String> {
+        let mut source = self.source.clone();
+
+        match self.entries.iter().find(|entry| entry.key == key) {
+            Some(entry) => source.replace_range(entry.span.clone(), &value.to_lua()),
+            None => {
+                let (parent, name) = match key.rsplit_once('.') {
+                    Some((parent, name)) => (parent, name),
+                    None => ("", key),
+                };
+                let table = match self.tables.iter().find(|table| table.key == parent) {
+                    Some(table) => table,
+                    None => return Err(format!("table {parent} doesn't exist")),
+                };
+
+                let line_start = source[..table.close].rfind('\n').map(|pos| pos + 1).unwrap_or(0);
+                let (insert_at, indent) = match source[line_start..table.close].trim().is_empty() {
+                    true => {
+                        let close_indent = &source[line_start..table.close];
+                        let indent = self.field_indent(table).unwrap_or(format!("{close_indent}    "));
+                        (line_start, indent)
+                    }
+                    false => (table.close, String::from(" ")),
+                };
+
+                let mut field = format!("{indent}{name} = {},", value.to_lua());
+                if insert_at == line_start {
+                    field.push('\n');
+                } else {
+                    field.push(' ');
+                }
+                source.insert_str(insert_at, &field);
+
+                if let Some((last_end, false)) = table.last_field {
+                    source.insert(last_end, ',');
+                }
+            }
+        }
+
+        *self = Self::parse(&source)?;
+        Ok(())
+    }
+
+    fn field_indent(&self, table: &SandboxTable) -> Option<String> {
+        let (last_end, _) = table.last_field?;
+        let line_start = self.source[..last_end].rfind('\n')? + 1;
+        let line = &self.source[line_start..last_end];
+        let indent_len = line.len() - line.trim_start().len();
+        match line_start > table.open {
+            true => Some(line[..indent_len].to_owned()),
+            false => None,
+        }
+    }
+
+    pub fn diff(&self, desired: &BTreeMap<String, SandboxValue>) -> Vec<SandboxChange> {
+        let mut changes = vec![];
+
+        for (key, desired_value) in desired {
+            let current = self.get(key).cloned();
+            let unchanged = match &current {
+                Some(current) => current.same_as(desired_value),
+                None => false,
+            };
+            if !unchanged {
+                changes.push(SandboxChange {
+                    key: key.clone(),
+                    current,
+                    desired: desired_value.clone(),
+                });
+            }
+        }
+
+        changes
+    }
+
+    pub fn to_lua(&self) -> &str {
+        &self.source
+    }
+}
+
+/// `servertest.ini` -> `servertest_SandboxVars.lua`
+pub fn sandboxvars_path(ini_path: &Path) -> PathBuf {
+    let server_name = ini_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    ini_path.with_file_name(format!("{server_name}_SandboxVars.lua"))
+}
+
+/// Values of the selected preset, overridden by the explicit `values` block.
+pub fn desired_values(settings: &SandboxSettings) -> Result<BTreeMap<String, SandboxValue>, String> {
+    let mut desired = BTreeMap::new();
+
+    if let Some(preset) = &settings.preset {
+        match settings.presets.get(preset) {
+            Some(preset_values) => desired.extend(preset_values.clone()),
+            None => return Err(format!("sandbox preset {preset} is not defined")),
+        }
+    }
+
+    desired.extend(settings.values.clone());
+    Ok(desired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SANDBOX_VARS: &str = "SandboxVars = {\n    VERSION = 5,\n    Zombies = 4,\n    -- keep me\n    StartTime = 2.5,\n    ZombieLore = {\n        Speed = 2,\n        Strength = 2\n    },\n    ['Some.Mod'] = {\n        Label = \"a \\\"quoted\\\" value\",\n    },\n}\n";
+
+    #[test]
+    fn sandbox_vars_parse_test() {
+        let sandbox_vars = SandboxVars::parse(SANDBOX_VARS).unwrap();
+        assert_eq!(Some(&SandboxValue::Integer(4)), sandbox_vars.get("Zombies"));
+        assert_eq!(Some(&SandboxValue::Float(2.5)), sandbox_vars.get("StartTime"));
+        assert_eq!(Some(&SandboxValue::Integer(2)), sandbox_vars.get("ZombieLore.Strength"));
+        assert_eq!(
+            Some(&SandboxValue::String("a \"quoted\" value".to_owned())),
+            sandbox_vars.get("Some.Mod.Label")
+        );
+    }
+
+    #[test]
+    fn sandbox_vars_set_keeps_formatting_test() {
+        let mut sandbox_vars = SandboxVars::parse(SANDBOX_VARS).unwrap();
+        sandbox_vars.set("Zombies", &SandboxValue::Integer(1)).unwrap();
+        sandbox_vars.set("ZombieLore.Toughness", &SandboxValue::Integer(3)).unwrap();
+
+        let expected = SANDBOX_VARS
+            .replace("Zombies = 4", "Zombies = 1")
+            .replace("Strength = 2\n", "Strength = 2,\n        Toughness = 3,\n");
+        assert_eq!(expected, sandbox_vars.to_lua());
+    }
+
+    #[test]
+    fn sandbox_vars_diff_test() {
+        let sandbox_vars = SandboxVars::parse(SANDBOX_VARS).unwrap();
+        let mut desired = BTreeMap::new();
+        desired.insert("StartTime".to_owned(), SandboxValue::Float(2.5));
+        desired.insert("VERSION".to_owned(), SandboxValue::Float(5.0));
+        desired.insert("ZombieLore.Speed".to_owned(), SandboxValue::Integer(3));
+
+        let changes = sandbox_vars.diff(&desired);
+        assert_eq!(1, changes.len());
+        assert_eq!("ZombieLore.Speed", changes[0].key);
+    }
+}
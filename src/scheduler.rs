@@ -0,0 +1,454 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use log::{error, info, warn};
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use tokio::process::Command;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::backup;
+use crate::config::{NotifyEvent, ScheduleSettings, ScheduledTask, TaskAction, ZSOConfig};
+use crate::notify::{self, Notification};
+use crate::operator;
+use crate::rcon::RconClient;
+use crate::reboot_messages::RebootNotice;
+
+pub const SCHEDULE_STATE_FILE_NAME: &str = "zso_schedule.json";
+
+/// Last run of every task, to notice runs missed while zso wasn't running.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleState {
+    pub last_runs: BTreeMap<String, i64>,
+}
+
+/// Sits next to the config, like `zso.lock`.
+pub fn state_path(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(SCHEDULE_STATE_FILE_NAME)
+}
+
+async fn load_state(path: &Path) -> ScheduleState {
+    match tokio::fs::read_to_string(path).await {
+        Ok(state_data) => serde_json::from_str(&state_data).unwrap_or_else(|e| {
+            warn!("Failed to parse {} - {}", path.display(), e);
+            ScheduleState::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ScheduleState::default(),
+        Err(e) => {
+            warn!("Failed to open {} - {}", path.display(), e);
+            ScheduleState::default()
+        }
+    }
+}
+
+async fn save_state(path: &Path, state: &ScheduleState) {
+    let state_data = match serde_json::to_string_pretty(state) {
+        Ok(state_data) => state_data,
+        Err(e) => {
+            error!("Failed to serialize schedule state - {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(path, state_data.as_bytes()).await {
+        error!("Failed to write {} - {}", path.display(), e);
+    }
+}
+
+/// Crontab weekday numbers (0 or 7 = Sunday) to the cron crate's (1 = Sunday). Names are kept.
+fn crontab_weekdays(field: &str) -> Result<String, String> {
+    let weekday = |value: &str| match value.parse::<u32>() {
+        Ok(day) if day <= 7 => Ok(day % 7 + 1),
+        _ => Err(format!("bad weekday {value}")),
+    };
+
+    let mut parts = vec![];
+    for part in field.split(',') {
+        let (days, step) = match part.split_once('/') {
+            Some((days, step)) => (days, Some(step)),
+            None => (part, None),
+        };
+        let step = step.map(|step| format!("/{step}")).unwrap_or_default();
+
+        if days == "*" || days.chars().any(|c| c.is_ascii_alphabetic()) {
+            parts.push(part.to_owned());
+            continue;
+        }
+        match days.split_once('-') {
+            Some((first, last)) => match (weekday(first)?, last) {
+                (1, "7") => parts.push(format!("1-7{step}")),
+                // Sunday as 7 comes first for the cron crate
+                (first, "7") if step.is_empty() => parts.extend([format!("{first}-7"), "1".to_owned()]),
+                (_, "7") => return Err(format!("weekday range {days} with a step - use weekday names")),
+                (first, last) => parts.push(format!("{first}-{}{step}", weekday(last)?)),
+            },
+            None => parts.push(format!("{}{}", weekday(days)?, step)),
+        }
+    }
+
+    Ok(parts.join(","))
+}
+
+/// Takes classic 5 field crontab lines as well as the cron crate's 6 and 7 field ones.
+/// Weekday numbers of 5 field lines count like crontab, 0 = Sunday.
+pub fn parse_cron(expression: &str) -> Result<Schedule, String> {
+    let fields = expression.split_whitespace().collect::<Vec<&str>>();
+    let expression = match fields.len() {
+        5 => format!("0 {} {}", fields[..4].join(" "), crontab_weekdays(fields[4])?),
+        _ => expression.to_owned(),
+    };
+    Schedule::from_str(&expression).map_err(|e| format!("bad cron expression {expression} - {e}"))
+}
+
+pub fn timezone(settings: &ScheduleSettings) -> Result<Tz, String> {
+    settings
+        .timezone
+        .parse::<Tz>()
+        .map_err(|e| format!("bad timezone {} - {}", settings.timezone, e))
+}
+
+/// The latest fire time after `last_run` that is already in the past.
+pub fn missed_run(schedule: &Schedule, tz: &Tz, last_run: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    schedule
+        .after(&last_run.with_timezone(tz))
+        .take_while(|fire_time| fire_time.with_timezone(&Utc) <= now)
+        .last()
+        .map(|fire_time| fire_time.with_timezone(&Utc))
+}
+
+/// Prints the next fire times of every task.
+pub fn list(settings: &ScheduleSettings, count: usize) -> Result<(), String> {
+    let tz = timezone(settings)?;
+
+    for task in &settings.tasks {
+        let schedule = parse_cron(&task.cron)?;
+        println!("{} ({}) - {}", task.name, task.cron, describe_action(&task.action));
+        for fire_time in schedule.upcoming(tz).take(count) {
+            println!("  {}", fire_time.format("%Y-%m-%d %H:%M:%S %Z"));
+        }
+    }
+
+    Ok(())
+}
+
+fn describe_action(action: &TaskAction) -> String {
+    match action {
+        TaskAction::RconCommand { command } => format!("RCON command {command}"),
+        TaskAction::Broadcast { message } => format!("broadcast \"{message}\""),
+        TaskAction::RebootCountdown => "reboot countdown".to_owned(),
+        TaskAction::Backup => "backup".to_owned(),
+        TaskAction::Refresh { ini, .. } => format!("refresh {}", ini.display()),
+    }
+}
+
+pub struct Scheduler {
+    config: &'static ZSOConfig,
+    config_path: PathBuf,
+    state_path: PathBuf,
+    state: Mutex<ScheduleState>,
+    /// Reboots, backups and refreshes touch the server, only one of them runs at a time.
+    exclusive: Mutex<()>,
+}
+
+impl Scheduler {
+    pub async fn new(config: &'static ZSOConfig, config_path: &Path) -> Self {
+        let state_path = state_path(config_path);
+        let state = load_state(&state_path).await;
+
+        Self {
+            config,
+            config_path: config_path.to_path_buf(),
+            state_path,
+            state: Mutex::new(state),
+            exclusive: Mutex::new(()),
+        }
+    }
+
+    async fn run_action(&self, task: &ScheduledTask) -> Result<(), String> {
+        match &task.action {
+            TaskAction::RconCommand { command } => {
+                let rcon_settings = self.config.rcon.as_ref().ok_or("RCON is not configured")?;
+                let reply = RconClient::connect(rcon_settings).await?.exec(command).await?;
+                info!("{}: {}", task.name, reply.trim());
+                Ok(())
+            }
+            TaskAction::Broadcast { message } => {
+                let rcon_settings = self.config.rcon.as_ref().ok_or("RCON is not configured")?;
+                RconClient::connect(rcon_settings).await?.broadcast(message).await
+            }
+            TaskAction::RebootCountdown => {
+                let server_settings = self
+                    .config
+                    .server_settings
+                    .as_ref()
+                    .ok_or("server_settings are not configured")?;
+                operator::reboot_server(self.config, server_settings, &RebootNotice::new("scheduled"), &mut None)
+                    .await
+                    .map_err(|_| "reboot failed".to_owned())
+            }
+            TaskAction::Backup => backup::create_backup(self.config, "scheduled")
+                .await
+                .map(|_| ())
+                .map_err(|_| "backup failed".to_owned()),
+            TaskAction::Refresh { ini, maps, update } => {
+                let exe = std::env::current_exe().map_err(|e| format!("can't find the zso binary - {e}"))?;
+                let mut command = Command::new(exe);
+                command.arg("--config").arg(&self.config_path).arg("--ini").arg(ini);
+                if *maps {
+                    command.arg("--maps");
+                }
+                if *update {
+                    command.arg("--update");
+                }
+
+                match command.status().await {
+                    Ok(status) if status.success() => Ok(()),
+                    Ok(status) => Err(format!("refresh exited with {status}")),
+                    Err(e) => Err(format!("failed to start refresh - {e}")),
+                }
+            }
+        }
+    }
+
+    /// `Err` when the task needs the exclusive lock and another task holds it.
+    fn lock_for(&self, task: &ScheduledTask) -> Result<Option<MutexGuard<'_, ()>>, ()> {
+        match task.action {
+            TaskAction::RconCommand { .. } | TaskAction::Broadcast { .. } => Ok(None),
+            _ => self.exclusive.try_lock().map(Some).map_err(|_| ()),
+        }
+    }
+
+    async fn run_task(&self, task: &ScheduledTask) {
+        let _guard = match self.lock_for(task) {
+            Ok(guard) => guard,
+            Err(_) => {
+                warn!("Skipping {} - another reboot, backup or refresh is still running", task.name);
+                return;
+            }
+        };
+
+        info!("Running scheduled task {}", task.name);
+        match self.run_action(task).await {
+            Ok(_) => info!("Scheduled task {} finished", task.name),
+            Err(e) => {
+                // not recorded, a restart before the next fire time runs it again with run_missed
+                error!("Scheduled task {} failed - {}", task.name, e);
+                // a failed reboot already sent reboot_failed
+                let reboot_failed = matches!(task.action, TaskAction::RebootCountdown) && self.config.server_settings.is_some();
+                if !reboot_failed {
+                    let title = format!("Scheduled task {} failed", task.name);
+                    notify::notify(self.config, Notification::new(NotifyEvent::TaskFailed, title, e)).await;
+                }
+                return;
+            }
+        }
+
+        let mut state = self.state.lock().await;
+        state.last_runs.insert(task.name.clone(), Utc::now().timestamp());
+        save_state(&self.state_path, &state).await;
+    }
+
+    async fn task_loop(&self, task: &ScheduledTask, schedule: Schedule, tz: Tz) {
+        let last_run = self.state.lock().await.last_runs.get(&task.name).copied();
+        let now = Utc::now();
+
+        match last_run.and_then(|last_run| DateTime::from_timestamp(last_run, 0)) {
+            Some(last_run) => {
+                if let Some(missed) = missed_run(&schedule, &tz, last_run, now) {
+                    match task.run_missed {
+                        true => {
+                            info!("{} missed its run at {}, running it now", task.name, missed);
+                            self.run_task(task).await;
+                        }
+                        false => info!("{} missed its run at {}, waiting for the next one", task.name, missed),
+                    }
+                }
+            }
+            None => {
+                // first start with this task, nothing was missed
+                let mut state = self.state.lock().await;
+                state.last_runs.insert(task.name.clone(), now.timestamp());
+                save_state(&self.state_path, &state).await;
+            }
+        }
+
+        loop {
+            // fire times that passed while the previous run was going are dropped, not queued
+            let next = match schedule.upcoming(tz).next() {
+                Some(next) => next,
+                None => {
+                    info!("{} has no more fire times", task.name);
+                    return;
+                }
+            };
+            info!("{} runs next at {}", task.name, next);
+
+            let wait = (next.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            self.run_task(task).await;
+        }
+    }
+
+    /// Runs every task on its schedule until zso is stopped.
+    pub async fn run(self, settings: &'static ScheduleSettings) -> Result<(), ()> {
+        let tz = match timezone(settings) {
+            Ok(tz) => tz,
+            Err(e) => {
+                error!("{}", e);
+                return Err(());
+            }
+        };
+
+        let mut schedules = vec![];
+        for task in &settings.tasks {
+            match parse_cron(&task.cron) {
+                Ok(schedule) => schedules.push((task, schedule)),
+                Err(e) => {
+                    error!("Task {} - {}", task.name, e);
+                    return Err(());
+                }
+            }
+        }
+
+        let scheduler = Arc::new(self);
+        let mut handles = vec![];
+        for (task, schedule) in schedules {
+            let scheduler = scheduler.clone();
+            handles.push(tokio::spawn(async move {
+                scheduler.task_loop(task, schedule, tz).await;
+            }));
+        }
+
+        for handle in handles {
+            let _ = handle.await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rcon::tests::fake_server;
+
+    fn task(name: &str, action: TaskAction) -> ScheduledTask {
+        ScheduledTask {
+            name: name.to_owned(),
+            cron: "0 4 * * *".to_owned(),
+            action,
+            ..Default::default()
+        }
+    }
+
+    async fn scheduler(config: ZSOConfig, name: &str) -> (Scheduler, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("zso-scheduler-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config: &'static ZSOConfig = Box::leak(Box::new(config));
+        (Scheduler::new(config, &dir.join("zso.yaml")).await, dir)
+    }
+
+    #[test]
+    fn cron_schedule_test() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        let schedule = parse_cron("0 4 * * Mon,Thu").unwrap();
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc);
+
+        // Monday 2026-10-19 04:00 Berlin was missed, the Thursday one is still ahead
+        assert_eq!(
+            Some(at("2026-10-19T02:00:00Z")),
+            missed_run(&schedule, &tz, at("2026-10-17T12:00:00Z"), at("2026-10-20T12:00:00Z"))
+        );
+        assert_eq!(
+            None,
+            missed_run(&schedule, &tz, at("2026-10-19T03:00:00Z"), at("2026-10-20T12:00:00Z"))
+        );
+        assert!(parse_cron("0 4 * *").is_err());
+    }
+
+    #[test]
+    fn crontab_weekdays_test() {
+        let tz: Tz = "UTC".parse().unwrap();
+        // Sunday
+        let start = DateTime::parse_from_rfc3339("2026-10-18T12:00:00Z").unwrap().with_timezone(&tz);
+        let weekdays = |expression: &str| {
+            parse_cron(expression)
+                .unwrap()
+                .after(&start)
+                .take(7)
+                .map(|fire_time| fire_time.format("%a").to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(vec!["Mon", "Tue", "Wed", "Thu", "Fri", "Mon", "Tue"], weekdays("0 4 * * 1-5"));
+        assert_eq!(vec!["Sun"; 7], weekdays("0 4 * * 0"));
+        assert_eq!(vec!["Sun"; 7], weekdays("0 4 * * 7"));
+        assert_eq!(vec!["Fri", "Sat", "Sun", "Fri", "Sat", "Sun", "Fri"], weekdays("0 4 * * 5-7"));
+        assert_eq!(vec!["Mon", "Wed", "Fri", "Mon", "Wed", "Fri", "Mon"], weekdays("0 4 * * 1-5/2"));
+        assert_eq!(vec!["Mon", "Thu", "Mon", "Thu", "Mon", "Thu", "Mon"], weekdays("0 4 * * Mon,4"));
+        assert_eq!(
+            Ok("1-5,Sat,1-7,1,6-7,1".to_owned()),
+            crontab_weekdays("0-4,Sat,0-7,7,5-7")
+        );
+        assert!(parse_cron("0 4 * * 8").is_err());
+        assert!(parse_cron("0 4 * * 5-7/2").is_err());
+    }
+
+    #[tokio::test]
+    async fn run_action_test() {
+        let (without_rcon, dir) = scheduler(ZSOConfig::default(), "actions").await;
+        let command = task("save", TaskAction::RconCommand { command: "save".to_owned() });
+        let broadcast = task("hello", TaskAction::Broadcast { message: "hello".to_owned() });
+
+        assert_eq!(Err("RCON is not configured".to_owned()), without_rcon.run_action(&command).await);
+        assert_eq!(Err("RCON is not configured".to_owned()), without_rcon.run_action(&broadcast).await);
+        assert_eq!(
+            Err("server_settings are not configured".to_owned()),
+            without_rcon.run_action(&task("reboot", TaskAction::RebootCountdown)).await
+        );
+        assert_eq!(
+            Err("backup failed".to_owned()),
+            without_rcon.run_action(&task("backup", TaskAction::Backup)).await
+        );
+
+        let config = ZSOConfig {
+            rcon: Some(fake_server(0).await),
+            ..Default::default()
+        };
+        let (with_rcon, _) = scheduler(config, "actions").await;
+        assert_eq!(Ok(()), with_rcon.run_action(&command).await);
+        assert_eq!(Ok(()), with_rcon.run_action(&broadcast).await);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn run_task_test() {
+        let config = ZSOConfig {
+            rcon: Some(fake_server(0).await),
+            ..Default::default()
+        };
+        let (scheduler, dir) = scheduler(config, "tasks").await;
+        let backup = task("backup", TaskAction::Backup);
+        let save = task("save", TaskAction::RconCommand { command: "save".to_owned() });
+
+        // reboots, backups and refreshes are skipped while another one runs, RCON commands don't wait
+        let guard = scheduler.lock_for(&backup).unwrap();
+        assert!(guard.is_some());
+        assert!(scheduler.lock_for(&task("reboot", TaskAction::RebootCountdown)).is_err());
+        assert!(matches!(scheduler.lock_for(&save), Ok(None)));
+        drop(guard);
+
+        scheduler.run_task(&save).await;
+        // failed runs are not recorded
+        scheduler.run_task(&backup).await;
+        assert!(scheduler.lock_for(&backup).is_ok());
+
+        let state = load_state(&scheduler.state_path).await;
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(vec!["save"], state.last_runs.keys().collect::<Vec<&String>>());
+        assert_eq!(state, *scheduler.state.lock().await);
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use log::{error, info, warn};
+use regex::Regex;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::config::RconSettings;
+
+const MAX_INT: i64 = i32::MAX as i64;
+/// Keys written from the resolved workshop items, `server_options` would overwrite them.
+const MANAGED_KEYS: [&str; 3] = ["WorkshopItems", "Mods", "Map"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IniType {
+    Bool,
+    Integer { min: i64, max: i64 },
+    Float { min: f64, max: f64 },
+    String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IniKey {
+    pub name: &'static str,
+    pub kind: IniType,
+}
+
+const fn bool_key(name: &'static str) -> IniKey {
+    IniKey { name, kind: IniType::Bool }
+}
+
+const fn int_key(name: &'static str, min: i64, max: i64) -> IniKey {
+    IniKey { name, kind: IniType::Integer { min, max } }
+}
+
+const fn float_key(name: &'static str, min: f64, max: f64) -> IniKey {
+    IniKey { name, kind: IniType::Float { min, max } }
+}
+
+const fn string_key(name: &'static str) -> IniKey {
+    IniKey { name, kind: IniType::String }
+}
+
+/// Options of the dedicated server `<server>.ini` (build 41), with the ranges the game accepts.
+pub const KNOWN_KEYS: &[IniKey] = &[
+    bool_key("PVP"),
+    bool_key("PauseEmpty"),
+    bool_key("GlobalChat"),
+    string_key("ChatStreams"),
+    bool_key("Open"),
+    string_key("ServerWelcomeMessage"),
+    bool_key("AutoCreateUserInWhiteList"),
+    bool_key("DisplayUserName"),
+    bool_key("ShowFirstAndLastName"),
+    string_key("SpawnPoint"),
+    bool_key("SafetySystem"),
+    bool_key("ShowSafety"),
+    int_key("SafetyToggleTimer", 0, 1000),
+    int_key("SafetyCooldownTimer", 0, 1000),
+    string_key("SpawnItems"),
+    int_key("DefaultPort", 0, 65535),
+    int_key("UDPPort", 0, 65535),
+    int_key("ResetID", 0, MAX_INT),
+    string_key("Mods"),
+    string_key("Map"),
+    bool_key("DoLuaChecksum"),
+    bool_key("DenyLoginOnOverloadedServer"),
+    bool_key("Public"),
+    string_key("PublicName"),
+    string_key("PublicDescription"),
+    int_key("MaxPlayers", 1, 100),
+    int_key("PingLimit", 100, MAX_INT),
+    int_key("HoursForLootRespawn", 0, MAX_INT),
+    int_key("MaxItemsForLootRespawn", 1, MAX_INT),
+    bool_key("ConstructionPreventsLootRespawn"),
+    bool_key("DropOffWhiteListAfterDeath"),
+    bool_key("NoFire"),
+    bool_key("AnnounceDeath"),
+    float_key("MinutesPerPage", 0.0, 60.0),
+    int_key("SaveWorldEveryMinutes", 0, MAX_INT),
+    bool_key("PlayerSafehouse"),
+    bool_key("AdminSafehouse"),
+    bool_key("SafehouseAllowTrepass"),
+    bool_key("SafehouseAllowFire"),
+    bool_key("SafehouseAllowLoot"),
+    bool_key("SafehouseAllowRespawn"),
+    int_key("SafehouseDaySurvivedToClaim", 0, MAX_INT),
+    int_key("SafeHouseRemovalTime", 0, MAX_INT),
+    bool_key("SafehouseAllowNonResidential"),
+    bool_key("AllowDestructionBySledgehammer"),
+    bool_key("SledgehammerOnlyInSafehouse"),
+    bool_key("KickFastPlayers"),
+    string_key("ServerPlayerID"),
+    int_key("RCONPort", 0, 65535),
+    string_key("RCONPassword"),
+    bool_key("DiscordEnable"),
+    string_key("DiscordToken"),
+    string_key("DiscordChannel"),
+    string_key("DiscordChannelID"),
+    string_key("Password"),
+    int_key("MaxAccountsPerUser", 0, MAX_INT),
+    bool_key("AllowCoop"),
+    bool_key("SleepAllowed"),
+    bool_key("SleepNeeded"),
+    bool_key("KnockedDownAllowed"),
+    bool_key("SneakModeHideFromOtherPlayers"),
+    string_key("WorkshopItems"),
+    string_key("SteamScoreboard"),
+    bool_key("SteamVAC"),
+    bool_key("UPnP"),
+    bool_key("VoiceEnable"),
+    float_key("VoiceMinDistance", 0.0, 100000.0),
+    float_key("VoiceMaxDistance", 0.0, 100000.0),
+    bool_key("Voice3D"),
+    float_key("SpeedLimit", 10.0, 150.0),
+    bool_key("LoginQueueEnabled"),
+    int_key("LoginQueueConnectTimeout", 20, 1200),
+    string_key("server_browser_announced_ip"),
+    bool_key("PlayerRespawnWithSelf"),
+    bool_key("PlayerRespawnWithOther"),
+    float_key("FastForwardMultiplier", 1.0, 100.0),
+    bool_key("DisableSafehouseWhenPlayerConnected"),
+    bool_key("Faction"),
+    int_key("FactionDaySurvivedToCreateTag", 0, MAX_INT),
+    int_key("FactionPlayersRequiredForTag", 1, MAX_INT),
+    bool_key("DisableRadioStaff"),
+    bool_key("DisableRadioAdmin"),
+    bool_key("DisableRadioGM"),
+    bool_key("DisableRadioOverseer"),
+    bool_key("DisableRadioModerator"),
+    bool_key("DisableRadioInvisible"),
+    string_key("ClientCommandFilter"),
+    string_key("ClientActionLogs"),
+    bool_key("PerkLogs"),
+    int_key("ItemNumbersLimitPerContainer", 0, 9000),
+    int_key("BloodSplatLifespanDays", 0, 365),
+    bool_key("AllowNonAsciiUsername"),
+    bool_key("BanKickGlobalSound"),
+    bool_key("RemovePlayerCorpsesOnCorpseRemoval"),
+    bool_key("TrashDeleteAll"),
+    bool_key("PVPMeleeWhileHitReaction"),
+    bool_key("MouseOverToSeeDisplayName"),
+    bool_key("HidePlayersBehindYou"),
+    float_key("PVPMeleeDamageModifier", 0.0, 500.0),
+    float_key("PVPFirearmDamageModifier", 0.0, 500.0),
+    float_key("CarEngineAttractionModifier", 0.0, 10.0),
+    bool_key("PlayerBumpPlayer"),
+    int_key("MapRemotePlayerVisibility", 1, 3),
+    int_key("BackupsCount", 1, 300),
+    bool_key("BackupsOnStart"),
+    bool_key("BackupsOnVersionChange"),
+    int_key("BackupsPeriod", 0, 1500),
+    bool_key("DisableVehicleTowing"),
+    bool_key("DisableTrailerTowing"),
+    bool_key("DisableBurntTowing"),
+    string_key("BadWordListFile"),
+    string_key("GoodWordListFile"),
+    int_key("BadWordPolicy", 1, 3),
+    string_key("BadWordReplacement"),
+    string_key("AvailableSpawnRegions"),
+    bool_key("UsernameDisguises"),
+    bool_key("HideDisguisedUserName"),
+    bool_key("SwitchZombiesOwnershipEachUpdate"),
+    int_key("ZombieUpdateMaxHighPriority", 0, MAX_INT),
+    int_key("ZombieUpdateDelta", 0, MAX_INT),
+    int_key("ZombieUpdateRadiusLowPriority", 0, MAX_INT),
+    int_key("ZombieUpdateRadiusHighPriority", 0, MAX_INT),
+    int_key("PingFrequency", 1, MAX_INT),
+];
+
+pub fn known_key(name: &str) -> Option<&'static IniKey> {
+    KNOWN_KEYS.iter().find(|key| key.name == name)
+}
+
+/// Value declared in `server_options`, written to the ini as plain text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IniValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl std::fmt::Display for IniValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IniValue::Bool(value) => write!(f, "{value}"),
+            IniValue::Integer(value) => write!(f, "{value}"),
+            IniValue::Float(value) => write!(f, "{value:?}"),
+            IniValue::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+pub fn validate_value(key: &IniKey, value: &str) -> Result<(), String> {
+    match key.kind {
+        IniType::Bool => match value {
+            "true" | "false" => Ok(()),
+            _ => Err(format!("expected true or false, got '{value}'")),
+        },
+        IniType::Integer { min, max } => match value.parse::<i64>() {
+            Ok(number) if number < min || number > max => {
+                Err(format!("{number} is out of range {min}..{max}"))
+            }
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("expected an integer, got '{value}'")),
+        },
+        IniType::Float { min, max } => match value.parse::<f64>() {
+            Ok(number) if number < min || number > max => {
+                Err(format!("{number} is out of range {min}..{max}"))
+            }
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("expected a number, got '{value}'")),
+        },
+        IniType::String => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl LintIssue {
+    fn new(severity: Severity, key: &str, message: String) -> Self {
+        Self {
+            severity,
+            key: key.to_owned(),
+            message,
+        }
+    }
+}
+
+/// `key=value` pairs of the ini in file order. Comments and blank lines are skipped.
+pub fn parse_ini(server_conf: &str) -> Vec<(String, String)> {
+    server_conf
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+        .collect()
+}
+
+pub fn lint(values: &[(String, String)], rcon: Option<&RconSettings>) -> Vec<LintIssue> {
+    let mut issues: Vec<LintIssue> = vec![];
+    let mut seen: Vec<&str> = vec![];
+
+    for (key, value) in values {
+        if seen.contains(&key.as_str()) {
+            issues.push(LintIssue::new(Severity::Warning, key, "key is set more than once, the last value wins".to_owned()));
+        }
+        seen.push(key);
+
+        match known_key(key) {
+            Some(known) => {
+                if let Err(message) = validate_value(known, value) {
+                    issues.push(LintIssue::new(Severity::Error, key, message));
+                }
+            }
+            None => issues.push(LintIssue::new(Severity::Warning, key, "unknown key".to_owned())),
+        }
+    }
+
+    let get = |name: &str| {
+        values
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let mut ports: Vec<(&str, u16)> = vec![];
+    for port_key in ["DefaultPort", "UDPPort", "RCONPort"] {
+        if let Some(port) = get(port_key).and_then(|port| port.parse::<u16>().ok()) {
+            if let Some((other_key, _)) = ports.iter().find(|(_, other)| *other == port) {
+                issues.push(LintIssue::new(
+                    Severity::Error,
+                    port_key,
+                    format!("port {port} is already used by {other_key}"),
+                ));
+            }
+            ports.push((port_key, port));
+        }
+    }
+
+    if let Some(rcon) = rcon {
+        match get("RCONPort") {
+            Some(port) if port != rcon.port.trim() => issues.push(LintIssue::new(
+                Severity::Error,
+                "RCONPort",
+                format!("{} doesn't match rcon.port {} from the config", port, rcon.port),
+            )),
+            None => issues.push(LintIssue::new(
+                Severity::Error,
+                "RCONPort",
+                "missing, but rcon is configured".to_owned(),
+            )),
+            _ => {}
+        }
+
+        match get("RCONPassword") {
+            Some("") | None => issues.push(LintIssue::new(
+                Severity::Error,
+                "RCONPassword",
+                "empty, the server won't accept RCON connections".to_owned(),
+            )),
+            Some(password) if password != rcon.password => issues.push(LintIssue::new(
+                Severity::Error,
+                "RCONPassword",
+                "doesn't match rcon.password from the config".to_owned(),
+            )),
+            _ => {}
+        }
+    }
+
+    issues
+}
+
+/// Checks options declared in the config before they are written.
+pub fn lint_options(options: &BTreeMap<String, IniValue>) -> Vec<LintIssue> {
+    let values = options
+        .iter()
+        .map(|(key, value)| (key.clone(), value.to_string()))
+        .collect::<Vec<(String, String)>>();
+    let mut issues = lint(&values, None);
+
+    for key in options.keys().filter(|key| MANAGED_KEYS.contains(&key.as_str())) {
+        issues.push(LintIssue::new(
+            Severity::Error,
+            key,
+            "set from workshop_settings, it can't be a server option".to_owned(),
+        ));
+    }
+
+    issues
+}
+
+pub fn report_issues(issues: &[LintIssue]) {
+    if issues.is_empty() {
+        info!("No issues found");
+        return;
+    }
+
+    for issue in issues {
+        match issue.severity {
+            Severity::Error => error!("{}: {}", issue.key, issue.message),
+            Severity::Warning => warn!("{}: {}", issue.key, issue.message),
+        }
+    }
+}
+
+/// Sets every declared option in place, appending keys the ini doesn't have yet.
+pub async fn update_server_options(ini_path: &Path, options: &BTreeMap<String, IniValue>) -> Result<(), ()> {
+    if options.is_empty() {
+        return Ok(());
+    }
+
+    let mut server_conf = match tokio::fs::read_to_string(ini_path).await {
+        Ok(server_conf) => server_conf,
+        Err(e) => {
+            error!("Failed to open ini file - {}", e);
+            return Err(());
+        }
+    };
+
+    for (key, value) in options {
+        let key_regex = Regex::new(&format!(r"(?m)^{}=.*$", regex::escape(key))).unwrap();
+        let line = format!("{key}={value}");
+
+        if key_regex.is_match(&server_conf) {
+            server_conf = key_regex.replace(&server_conf, regex::NoExpand(&line)).to_string();
+        } else {
+            if !server_conf.is_empty() && !server_conf.ends_with('\n') {
+                server_conf.push('\n');
+            }
+            server_conf += &line;
+            server_conf.push('\n');
+        }
+    }
+
+    match tokio::fs::write(ini_path, server_conf.as_bytes()).await {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("Failed to write ini file - {}", e);
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_value_test() {
+        assert!(validate_value(known_key("PVP").unwrap(), "true").is_ok());
+        assert!(validate_value(known_key("PVP").unwrap(), "yes").is_err());
+        assert!(validate_value(known_key("DefaultPort").unwrap(), "16261").is_ok());
+        assert_eq!(
+            Err("70000 is out of range 0..65535".to_owned()),
+            validate_value(known_key("DefaultPort").unwrap(), "70000")
+        );
+        assert!(validate_value(known_key("DefaultPort").unwrap(), "port").is_err());
+        assert!(validate_value(known_key("PublicName").unwrap(), "anything").is_ok());
+    }
+
+    #[test]
+    fn lint_test() {
+        let rcon = RconSettings {
+            host: "127.0.0.1".to_owned(),
+            port: "27015".to_owned(),
+            password: "secret".to_owned(),
+            ..Default::default()
+        };
+        let values = parse_ini("# comment\nPVP=maybe\nDefaultPort=16261\nUDPPort=16261\nRCONPort=27016\nRCONPassword=secret\nPVP=true\nNoSuchKey=1\n");
+        let issues = lint(&values, Some(&rcon));
+
+        let found = issues
+            .iter()
+            .map(|issue| (issue.severity, issue.key.as_str()))
+            .collect::<Vec<(Severity, &str)>>();
+        assert_eq!(
+            vec![
+                (Severity::Error, "PVP"),
+                (Severity::Warning, "PVP"),
+                (Severity::Warning, "NoSuchKey"),
+                (Severity::Error, "UDPPort"),
+                (Severity::Error, "RCONPort"),
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn lint_options_test() {
+        let options = BTreeMap::from([
+            ("PVP".to_owned(), IniValue::Bool(false)),
+            ("Mods".to_owned(), IniValue::String("Brita".to_owned())),
+            ("Map".to_owned(), IniValue::String("Muldraugh, KY".to_owned())),
+        ]);
+
+        let issues = lint_options(&options);
+        assert_eq!(2, issues.len());
+        assert!(issues.iter().all(|issue| issue.severity == Severity::Error && issue.key != "PVP"));
+    }
+
+    #[tokio::test]
+    async fn update_server_options_test() {
+        let ini_path = std::env::temp_dir().join(format!("zso-options-{}.ini", std::process::id()));
+        tokio::fs::write(&ini_path, "PVP=true\nPublicName=Old name\nMaxPlayers=32").await.unwrap();
+
+        let options = BTreeMap::from([
+            ("PVP".to_owned(), IniValue::Bool(false)),
+            ("PublicName".to_owned(), IniValue::String("$1 server".to_owned())),
+            ("SpeedLimit".to_owned(), IniValue::Float(70.0)),
+        ]);
+        let updated = update_server_options(&ini_path, &options).await;
+        let server_conf = tokio::fs::read_to_string(&ini_path).await.unwrap();
+        let _ = tokio::fs::remove_file(&ini_path).await;
+
+        assert_eq!(Ok(()), updated);
+        assert_eq!("PVP=false\nPublicName=$1 server\nMaxPlayers=32\nSpeedLimit=70.0\n", server_conf);
+    }
+}
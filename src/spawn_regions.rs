@@ -0,0 +1,288 @@
+use std::path::{Path, PathBuf};
+
+use log::{debug, error, info, warn};
+use regex::Regex;
+
+use crate::steam_api_client::ModData;
+use crate::zomboid_maps;
+
+/// Regions the game ships with, always written first.
+pub const VANILLA_REGIONS: [&str; 4] = ["Muldraugh, KY", "West Point, KY", "Rosewood, KY", "Riverside, KY"];
+
+/// Marks entries written by the operator, everything else inside the table is kept as is.
+const MANAGED_MARKER: &str = "-- zso";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnRegion {
+    pub name: String,
+    pub file: String,
+}
+
+impl SpawnRegion {
+    pub fn for_map(name: &str, map_folder: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            file: format!("media/maps/{map_folder}/spawnpoints.lua"),
+        }
+    }
+
+    fn to_lua(&self) -> String {
+        format!("{{ name = \"{}\", file = \"{}\" }},", self.name, self.file)
+    }
+}
+
+/// `servertest.ini` -> `servertest_spawnregions.lua`
+pub fn spawnregions_path(ini_path: &Path) -> PathBuf {
+    let server_name = ini_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    ini_path.with_file_name(format!("{server_name}_spawnregions.lua"))
+}
+
+/// Splits the body of the `return { ... }` table into its entries: whole `{ ... }` blocks with a trailing
+/// comma and their comment, and comments standing on their own.
+fn table_entries(spawnregions: &str) -> Vec<String> {
+    let body_start = match spawnregions
+        .find("return")
+        .and_then(|pos| spawnregions[pos..].find('{').map(|brace| pos + brace + 1))
+    {
+        Some(body_start) => body_start,
+        None => return vec![],
+    };
+
+    let bytes = spawnregions.as_bytes();
+    let line_end = |from: usize| spawnregions[from..].find('\n').map_or(spawnregions.len(), |end| from + end);
+    let mut entries: Vec<String> = vec![];
+    let mut depth = 1;
+    let mut entry_start = 0;
+    let mut quote: Option<u8> = None;
+    let mut pos = body_start;
+
+    while pos < bytes.len() {
+        let byte = bytes[pos];
+
+        if let Some(open) = quote {
+            match byte {
+                b'\\' => pos += 1,
+                _ if byte == open => quote = None,
+                _ => {}
+            }
+            pos += 1;
+            continue;
+        }
+
+        match byte {
+            b'"' | b'\'' => quote = Some(byte),
+            b'-' if bytes.get(pos + 1) == Some(&b'-') => {
+                let comment_end = line_end(pos);
+                if depth == 1 {
+                    entries.push(spawnregions[pos..comment_end].to_owned());
+                }
+                pos = comment_end;
+                continue;
+            }
+            b'{' => {
+                if depth == 1 {
+                    entry_start = pos;
+                }
+                depth += 1;
+            }
+            b'}' if depth == 1 => break,
+            b'}' => {
+                depth -= 1;
+                if depth == 1 {
+                    // a comment on the same line belongs to the entry, the comma is added where it's missing
+                    let rest_end = line_end(pos);
+                    let rest = spawnregions[pos + 1..rest_end].trim_start();
+                    let rest = rest.strip_prefix(',').unwrap_or(rest).trim();
+                    let block = &spawnregions[entry_start..=pos];
+                    match rest.starts_with("--") {
+                        true => {
+                            entries.push(format!("{block}, {rest}"));
+                            pos = rest_end;
+                        }
+                        false => {
+                            entries.push(format!("{block},"));
+                            pos += 1;
+                        }
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+
+    entries
+}
+
+/// Returns entries of the existing table that were added by hand, whole blocks even if they span lines.
+/// Vanilla regions and entries carrying the managed marker are dropped, they get regenerated.
+pub(crate) fn manual_entries(spawnregions: &str) -> Vec<String> {
+    let entry_re = Regex::new(r#"name\s*=\s*"(?P<name>[^"]*)""#).unwrap();
+
+    table_entries(spawnregions)
+        .into_iter()
+        .map(|entry| entry.trim().to_owned())
+        .filter(|entry| !entry.ends_with(MANAGED_MARKER))
+        .filter(|entry| {
+            entry.starts_with("--")
+                || !entry_re
+                    .captures(entry)
+                    .is_some_and(|captures| VANILLA_REGIONS.contains(&&captures["name"]))
+        })
+        .collect()
+}
+
+pub(crate) fn render_spawnregions(manual: &[String], map_regions: &[SpawnRegion]) -> String {
+    let mut lua = String::from("function SpawnRegions()\n\treturn {\n");
+
+    for name in VANILLA_REGIONS {
+        lua += &format!("\t\t{}\n", SpawnRegion::for_map(name, name).to_lua());
+    }
+
+    for line in manual {
+        lua += &format!("\t\t{line}\n");
+    }
+
+    for region in map_regions {
+        lua += &format!("\t\t{} {}\n", region.to_lua(), MANAGED_MARKER);
+    }
+
+    lua += "\t}\nend\n";
+    lua
+}
+
+/// Finds `spawnpoints.lua` for every map folder of the resolved workshop items.
+pub(crate) fn collect_map_regions(
+    mods_data: &[ModData],
+    content_dir: &Path,
+    maps_to_exclude: &[String],
+) -> Vec<SpawnRegion> {
+    let mut regions: Vec<SpawnRegion> = vec![];
+
+    for mod_data in mods_data {
+        for map_name in &mod_data.map_name {
+            if maps_to_exclude.contains(map_name) || VANILLA_REGIONS.contains(&map_name.as_str()) {
+                continue;
+            }
+
+            let map_dir = zomboid_maps::find_map_dirs(content_dir, mod_data.mod_id, map_name)
+                .into_iter()
+                .rev()
+                .find(|map_dir| map_dir.join("spawnpoints.lua").is_file());
+
+            let map_dir = match map_dir {
+                Some(map_dir) => map_dir,
+                None => {
+                    debug!("Map {} ({}) has no spawnpoints.lua", map_name, mod_data.mod_id);
+                    continue;
+                }
+            };
+
+            let name = zomboid_maps::read_map_coverage(&map_dir, map_name, Some(mod_data.mod_id))
+                .info
+                .title
+                .unwrap_or_else(|| map_name.clone());
+
+            let region = SpawnRegion::for_map(&name, map_name);
+            if !regions.contains(&region) {
+                regions.push(region);
+            }
+        }
+    }
+
+    regions
+}
+
+pub async fn update_spawnregions(
+    spawnregions_path: &Path,
+    mods_data: &[ModData],
+    content_dir: &Path,
+    maps_to_exclude: &[String],
+) -> Result<(), ()> {
+    let current = match tokio::fs::read_to_string(spawnregions_path).await {
+        Ok(current) => current,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            warn!("{} doesn't exist, creating it", spawnregions_path.display());
+            String::new()
+        }
+        Err(e) => {
+            error!("Failed to open spawnregions file - {}", e);
+            return Err(());
+        }
+    };
+
+    let manual = manual_entries(&current);
+    let map_regions = collect_map_regions(mods_data, content_dir, maps_to_exclude)
+        .into_iter()
+        .filter(|region| !manual.iter().any(|line| line.contains(&region.file)))
+        .collect::<Vec<SpawnRegion>>();
+
+    info!(
+        "Writing {} map spawn regions and {} manual entries to {}",
+        map_regions.len(),
+        manual.len(),
+        spawnregions_path.display()
+    );
+
+    match tokio::fs::write(spawnregions_path, render_spawnregions(&manual, &map_regions)).await {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            error!("Failed to write spawnregions file - {}", e);
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_entries_test() {
+        let spawnregions = r#"function SpawnRegions()
+	return {
+		{
+			name = "Muldraugh, KY",
+			file = "media/maps/Muldraugh, KY/spawnpoints.lua",
+		},
+		{ name = "Riverside, KY", file = "media/maps/Riverside, KY/spawnpoints.lua" },
+		-- { name = "Rosewood, KY", file = "media/maps/Rosewood, KY/spawnpoints.lua" },
+		{
+			name = "Event arena",
+			points = { unemployed = { { worldX = 40, worldY = 22, posX = 67, posY = 198, posZ = 0 } } },
+		}, -- for the weekend
+		{ name = "Bedford Falls", file = "media/maps/Bedford Falls/spawnpoints.lua" }, -- zso
+	}
+end
+"#;
+
+        assert_eq!(
+            vec![
+                "-- { name = \"Rosewood, KY\", file = \"media/maps/Rosewood, KY/spawnpoints.lua\" },",
+                "{\n\t\t\tname = \"Event arena\",\n\t\t\tpoints = { unemployed = { { worldX = 40, worldY = 22, posX = 67, posY = 198, posZ = 0 } } },\n\t\t}, -- for the weekend",
+            ],
+            manual_entries(spawnregions)
+        );
+
+        let one_line = r#"function SpawnRegions() return { { name = "Muldraugh, KY", file = "media/maps/Muldraugh, KY/spawnpoints.lua" }, { name = "Custom", file = "media/maps/Custom/spawnpoints.lua" } } end"#;
+        assert_eq!(
+            vec!["{ name = \"Custom\", file = \"media/maps/Custom/spawnpoints.lua\" },"],
+            manual_entries(one_line)
+        );
+        assert!(manual_entries("").is_empty());
+    }
+
+    #[test]
+    fn render_spawnregions_test() {
+        let manual = vec!["{ name = \"Custom\", file = \"media/maps/Custom/spawnpoints.lua\" },".to_owned()];
+        let rendered = render_spawnregions(&manual, &[SpawnRegion::for_map("Bedford Falls", "Bedford Falls")]);
+
+        // regenerating from its own output changes nothing
+        assert_eq!(manual, manual_entries(&rendered));
+        assert_eq!(rendered, render_spawnregions(&manual_entries(&rendered), &[SpawnRegion::for_map("Bedford Falls", "Bedford Falls")]));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin, Command};
+
+use crate::boot_check::{self, BootFailure};
+use crate::config::{NotifyEvent, StagingSettings, ZSOConfig};
+use crate::log_parser::LogTailer;
+use crate::notify::{self, Notification};
+use crate::server_ini::{self, IniValue};
+use crate::spawn_regions;
+use crate::steam_api_client::ModData;
+use crate::supervisor::ProcessGroup;
+use crate::zomboid_utils;
+
+pub const STAGING_SERVER_NAME: &str = "zso-staging";
+const STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The new WorkshopItems=, Mods= and Map= strings to try out.
+pub struct ModSet<'a> {
+    pub workshop_items: &'a str,
+    pub mods: &'a str,
+    pub maps: Option<&'a str>,
+    pub mods_data: &'a [ModData],
+}
+
+/// Only the lines that decide what gets loaded, the rest of the ini can't keep the server from booting.
+fn mod_lines(server_conf: &str) -> Vec<&str> {
+    server_conf
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("WorkshopItems=") || line.starts_with("Mods=") || line.starts_with("Map="))
+        .collect()
+}
+
+pub fn mod_lines_changed(current_conf: &str, staged_conf: &str) -> bool {
+    mod_lines(current_conf) != mod_lines(staged_conf)
+}
+
+/// Keeps the staging server off the real server's ports and out of the server browser.
+fn staging_options(settings: &StagingSettings) -> BTreeMap<String, IniValue> {
+    BTreeMap::from([
+        ("DefaultPort".to_owned(), IniValue::Integer(settings.port as i64)),
+        ("UDPPort".to_owned(), IniValue::Integer(settings.port as i64 + 1)),
+        ("RCONPort".to_owned(), IniValue::Integer(settings.port as i64 + 2)),
+        ("Public".to_owned(), IniValue::Bool(false)),
+        ("PublicName".to_owned(), IniValue::String("zso staging".to_owned())),
+    ])
+}
+
+fn start_script(config: &ZSOConfig, settings: &StagingSettings) -> Result<PathBuf, ()> {
+    let install_dir = config
+        .server_settings
+        .as_ref()
+        .and_then(|server_settings| server_settings.install_dir.as_deref());
+    let supervisor_script = config
+        .supervisor
+        .as_ref()
+        .and_then(|supervisor| supervisor.start_script.clone());
+
+    match (&settings.start_script, supervisor_script, install_dir) {
+        (Some(start_script), _, _) => Ok(start_script.clone()),
+        (None, Some(start_script), _) => Ok(start_script),
+        (None, None, Some(install_dir)) => Ok(install_dir.join("start-server.sh")),
+        (None, None, None) => {
+            error!("Set staging.start_script or server_settings.install_dir to run the staging server");
+            Err(())
+        }
+    }
+}
+
+/// Writes the staging server's ini, sandbox vars and spawn regions into `cache_dir/Server`.
+/// `Ok(false)` when the mod set is the same as the real server's.
+async fn prepare(config: &ZSOConfig, settings: &StagingSettings, ini_path: &Path, mod_set: &ModSet<'_>, cache_dir: &Path) -> Result<bool, ()> {
+    let current_conf = match tokio::fs::read_to_string(ini_path).await {
+        Ok(current_conf) => current_conf,
+        Err(e) => {
+            error!("Failed to open ini file - {}", e);
+            return Err(());
+        }
+    };
+
+    if let Err(e) = tokio::fs::create_dir_all(cache_dir.join("Server")).await {
+        error!("Failed to create {} - {}", cache_dir.display(), e);
+        return Err(());
+    }
+
+    let staged_ini = cache_dir.join("Server").join(format!("{STAGING_SERVER_NAME}.ini"));
+    if let Err(e) = tokio::fs::write(&staged_ini, current_conf.as_bytes()).await {
+        error!("Failed to write {} - {}", staged_ini.display(), e);
+        return Err(());
+    }
+    zomboid_utils::update_server_config(&staged_ini, mod_set.workshop_items, mod_set.mods, mod_set.maps).await?;
+
+    let staged_conf = tokio::fs::read_to_string(&staged_ini).await.unwrap_or_default();
+    if !mod_lines_changed(&current_conf, &staged_conf) {
+        return Ok(false);
+    }
+
+    server_ini::update_server_options(&staged_ini, &config.server_options).await?;
+    server_ini::update_server_options(&staged_ini, &staging_options(settings)).await?;
+
+    let server_name = ini_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    for suffix in ["_SandboxVars.lua", "_spawnregions.lua"] {
+        let source = ini_path.with_file_name(format!("{server_name}{suffix}"));
+        let target = staged_ini.with_file_name(format!("{STAGING_SERVER_NAME}{suffix}"));
+        match tokio::fs::copy(&source, &target).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to copy {} to the staging server - {}", source.display(), e),
+        }
+    }
+
+    if mod_set.maps.is_some() {
+        if let Some(content_dir) = &config.workshop_settings.content_dir {
+            spawn_regions::update_spawnregions(
+                &spawn_regions::spawnregions_path(&staged_ini),
+                mod_set.mods_data,
+                content_dir,
+                &config.workshop_settings.exclude.maps,
+            )
+            .await?;
+        }
+    }
+
+    Ok(true)
+}
+
+fn spawn(config: &ZSOConfig, settings: &StagingSettings, cache_dir: &Path) -> Result<Child, ()> {
+    let start_script = start_script(config, settings)?;
+    let server_log = match std::fs::File::create(settings.dir.join("server.log")) {
+        Ok(server_log) => server_log,
+        Err(e) => {
+            error!("Failed to create the staging server log - {}", e);
+            return Err(());
+        }
+    };
+    let server_errors = match server_log.try_clone() {
+        Ok(server_errors) => server_errors,
+        Err(e) => {
+            error!("Failed to create the staging server log - {}", e);
+            return Err(());
+        }
+    };
+
+    let mut command = Command::new(&start_script);
+    command
+        .arg(format!("-cachedir={}", cache_dir.display()))
+        .arg("-servername")
+        .arg(STAGING_SERVER_NAME)
+        // a fresh server asks for an admin password on the console otherwise
+        .arg("-adminpassword")
+        .arg(format!("zso{}", std::process::id()))
+        .args(&settings.args)
+        .stdin(Stdio::piped())
+        .stdout(server_log)
+        .stderr(server_errors)
+        .process_group(0)
+        .kill_on_drop(true);
+
+    if let Some(working_dir) = start_script.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        command.current_dir(working_dir);
+    }
+    let jvm_options = match (&settings.jvm_options, &config.supervisor) {
+        (jvm_options, Some(supervisor)) if jvm_options.is_empty() => &supervisor.jvm_options,
+        (jvm_options, _) => jvm_options,
+    };
+    if !jvm_options.is_empty() {
+        command.env("JAVA_TOOL_OPTIONS", jvm_options.join(" "));
+    }
+
+    match command.spawn() {
+        Ok(child) => Ok(child),
+        Err(e) => {
+            error!("Failed to start {} - {}", start_script.display(), e);
+            Err(())
+        }
+    }
+}
+
+/// `quit` on the console, then SIGKILL to the whole group.
+async fn stop(child: &mut Child, stdin: Option<ChildStdin>, group: &ProcessGroup) {
+    if let Some(mut stdin) = stdin {
+        let _ = stdin.write_all(b"quit\n").await;
+    }
+    if tokio::time::timeout(STOP_TIMEOUT, child.wait()).await.is_err() {
+        warn!("Staging server didn't quit within {}s, killing it", STOP_TIMEOUT.as_secs());
+        group.signal("KILL").await;
+        let _ = child.kill().await;
+    }
+}
+
+/// Boots a throwaway server with the new mod set and waits for it to start without mod errors.
+/// Does nothing when the mod set didn't change.
+pub async fn preflight(config: &ZSOConfig, settings: &StagingSettings, ini_path: &Path, mod_set: &ModSet<'_>) -> Result<(), ()> {
+    let cache_dir = settings.dir.join("Zomboid");
+    if let Err(e) = tokio::fs::remove_dir_all(&cache_dir).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            error!("Failed to clear {} - {}", cache_dir.display(), e);
+            return Err(());
+        }
+    }
+
+    if !prepare(config, settings, ini_path, mod_set, &cache_dir).await? {
+        info!("Mod set didn't change - skipping the staging server");
+        return Ok(());
+    }
+
+    info!("Starting the staging server on port {}", settings.port);
+    let mut child = spawn(config, settings, &cache_dir)?;
+    // the JVM is a child of the start script, it goes down with the group when this is dropped
+    let group = ProcessGroup::of(&child);
+    // `wait` closes the child's stdin, the server needs it open to take `quit`
+    let stdin = child.stdin.take();
+    let mut tailer = LogTailer::new(&cache_dir, true);
+    let timeout = Duration::from_secs(settings.boot_timeout_sec);
+
+    let outcome = tokio::select! {
+        outcome = boot_check::wait_for_log(&mut tailer, timeout) => outcome,
+        status = child.wait() => Err(BootFailure {
+            reason: match status {
+                Ok(status) => format!("the staging server exited with {status}"),
+                Err(e) => format!("lost the staging server - {e}"),
+            },
+            errors: vec![],
+        }),
+    };
+    stop(&mut child, stdin, &group).await;
+
+    match outcome {
+        Ok(_) => {
+            info!("Staging server started with the new mod set");
+            let _ = tokio::fs::remove_dir_all(&cache_dir).await;
+            Ok(())
+        }
+        Err(failure) => {
+            error!("Staging server failed - {}", failure);
+            error!("Its logs are in {}", settings.dir.display());
+            let title = "New mod set failed on the staging server";
+            notify::notify(config, Notification::new(NotifyEvent::PreflightFailed, title, &failure.reason).details(&failure.errors)).await;
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mod_lines_test() {
+        let current = "PublicName=My server\nWorkshopItems=1;2\nMods=a;b\nMap=Muldraugh, KY\n";
+
+        assert!(!mod_lines_changed(
+            current,
+            "PublicName=zso staging\nWorkshopItems=1;2\nMods=a;b\nMap=Muldraugh, KY\nDefaultPort=16300\n"
+        ));
+        assert!(mod_lines_changed(current, "PublicName=My server\nWorkshopItems=1;2;3\nMods=a;b;c\nMap=Muldraugh, KY\n"));
+    }
+}
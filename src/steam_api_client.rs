@@ -9,7 +9,11 @@ pub struct ModData {
     pub mod_id: u64,
     pub mod_name: Vec<String>,
     pub map_name: Vec<String>,
-    pub last_updated: u64 //change to DateTime?
+    pub last_updated: u64, //change to DateTime?
+    pub title: String,
+    pub collections: Vec<u64>, //collections that list this item
+    pub preview_url: String,
+    pub file_size: u64,
 }
 
 pub struct SteamApiClient {
@@ -40,7 +44,16 @@ impl SteamApiClient {
     }
 
     pub async fn get_list_of_mods_in_collections(&self, collections_id: Vec<u64>) -> Vec<u64> {
-        let mut list_of_mods: Vec<u64> = vec![];
+        self.get_collections_children(collections_id)
+            .await
+            .into_iter()
+            .flat_map(|(_, children)| children)
+            .collect()
+    }
+
+    /// Same as `get_list_of_mods_in_collections`, but keeps track of which collection lists which item.
+    pub async fn get_collections_children(&self, collections_id: Vec<u64>) -> Vec<(u64, Vec<u64>)> {
+        let mut list_of_mods: Vec<(u64, Vec<u64>)> = vec![];
         let mut request_params: Vec<(String, String)> = vec![];
 
         request_params.push((
@@ -83,9 +96,12 @@ impl SteamApiClient {
                 1 => {
                     match collection_data.children {
                         Some(mods_data) => {
+                            let collection_id = collection_data.publishedfileid.parse::<u64>().unwrap();
+                            let mut children: Vec<u64> = vec![];
                             for mod_data in mods_data {
-                                list_of_mods.push(mod_data.publishedfileid.parse::<u64>().unwrap())
+                                children.push(mod_data.publishedfileid.parse::<u64>().unwrap())
                             }
+                            list_of_mods.push((collection_id, children));
                         },
                         None => {
                             warn!("Collection {} is empty.",collection_data.publishedfileid);
@@ -178,6 +194,9 @@ impl SteamApiClient {
                 let mut mod_data = ModData::default();  
                 mod_data.mod_id = full_mod_data.publishedfileid.parse::<u64>().unwrap();
                 mod_data.last_updated = full_mod_data.time_updated;
+                mod_data.title = full_mod_data.title.clone();
+                mod_data.preview_url = full_mod_data.preview_url.clone();
+                mod_data.file_size = full_mod_data.file_size;
 
                 // if &mod_name_result.count() == 0 {
                 //     
@@ -0,0 +1,315 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use regex::Regex;
+use tokio::process::Command;
+
+use crate::config::SteamCmdSettings;
+
+pub const ZOMBOID_APP_ID: u64 = 108600;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DownloadResult {
+    Downloaded { path: PathBuf, bytes: u64 },
+    Failed { reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemDownload {
+    pub workshop_id: u64,
+    pub result: DownloadResult,
+    pub attempts: u32,
+}
+
+/// Picks per-item results out of steamcmd output. Items steamcmd never mentioned are left out.
+pub(crate) fn parse_download_output(output: &str) -> Vec<(u64, DownloadResult)> {
+    let success_re =
+        Regex::new(r#"Success\. Downloaded item (?P<id>\d+) to "(?P<path>[^"]+)" \((?P<bytes>\d+) bytes\)"#).unwrap();
+    let failure_re = Regex::new(r"ERROR! Download item (?P<id>\d+) failed \((?P<reason>[^)]*)\)").unwrap();
+
+    let mut results: Vec<(u64, DownloadResult)> = vec![];
+
+    for line in output.lines() {
+        if let Some(captures) = success_re.captures(line) {
+            results.push((
+                captures["id"].parse().unwrap(),
+                DownloadResult::Downloaded {
+                    path: PathBuf::from(&captures["path"]),
+                    bytes: captures["bytes"].parse().unwrap_or_default(),
+                },
+            ));
+        } else if let Some(captures) = failure_re.captures(line) {
+            results.push((
+                captures["id"].parse().unwrap(),
+                DownloadResult::Failed {
+                    reason: captures["reason"].to_owned(),
+                },
+            ));
+        }
+    }
+
+    results
+}
+
+fn content_dir_is_populated(path: &Path) -> bool {
+    match std::fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => false,
+    }
+}
+
+pub struct SteamCmd {
+    settings: SteamCmdSettings,
+}
+
+impl SteamCmd {
+    pub fn new(settings: SteamCmdSettings) -> Self {
+        Self { settings }
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.settings.path);
+
+        if let Some(install_dir) = &self.settings.install_dir {
+            command.arg("+force_install_dir").arg(install_dir);
+        }
+        command.arg("+login").arg(&self.settings.login);
+        command
+    }
+
+    async fn output(&self, mut command: Command) -> Result<String, String> {
+        command.arg("+quit");
+
+        debug!("Running {:?}", command);
+
+        let output = match command.output().await {
+            Ok(output) => output,
+            Err(e) => return Err(format!("failed to start {} - {}", self.settings.path.display(), e)),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() {
+            warn!("steamcmd exited with {}", output.status);
+        }
+
+        Ok(stdout)
+    }
+
+    async fn run(&self, workshop_ids: &[u64], validate: bool) -> Result<String, String> {
+        let mut command = self.command();
+
+        for workshop_id in workshop_ids {
+            command
+                .arg("+workshop_download_item")
+                .arg(ZOMBOID_APP_ID.to_string())
+                .arg(workshop_id.to_string());
+            if validate {
+                command.arg("validate");
+            }
+        }
+
+        self.output(command).await
+    }
+
+    /// Raw `app_info_print` output, refreshed from steam first.
+    pub async fn app_info(&self, app_id: u64) -> Result<String, String> {
+        let mut command = self.command();
+        command
+            .arg("+app_info_update")
+            .arg("1")
+            .arg("+app_info_print")
+            .arg(app_id.to_string());
+
+        self.output(command).await
+    }
+
+    /// Where steamcmd puts the item when it doesn't tell us.
+    fn expected_content_dir(&self, workshop_id: u64) -> Option<PathBuf> {
+        let install_dir = self.settings.install_dir.as_ref()?;
+        Some(
+            install_dir
+                .join("steamapps/workshop/content")
+                .join(ZOMBOID_APP_ID.to_string())
+                .join(workshop_id.to_string()),
+        )
+    }
+
+    /// Downloads every item in one steamcmd session, then retries the failed ones one by one.
+    pub async fn download_items(&self, workshop_ids: &[u64], validate: bool) -> Vec<ItemDownload> {
+        let mut downloads: Vec<ItemDownload> = workshop_ids
+            .iter()
+            .map(|workshop_id| ItemDownload {
+                workshop_id: *workshop_id,
+                result: DownloadResult::Failed {
+                    reason: "not attempted".to_owned(),
+                },
+                attempts: 0,
+            })
+            .collect();
+
+        let mut pending: Vec<u64> = workshop_ids.to_vec();
+
+        for attempt in 0..=self.settings.retries {
+            if pending.is_empty() {
+                break;
+            }
+
+            if attempt > 0 {
+                let backoff = Duration::from_secs(self.settings.retry_delay_sec * attempt as u64);
+                warn!(
+                    "Retrying {} failed downloads in {}s (attempt {} of {})",
+                    pending.len(),
+                    backoff.as_secs(),
+                    attempt,
+                    self.settings.retries
+                );
+                tokio::time::sleep(backoff).await;
+            }
+
+            // a single broken item can abort the whole session, so retries go one item at a time
+            let batches: Vec<Vec<u64>> = match attempt {
+                0 => vec![pending.clone()],
+                _ => pending.iter().map(|workshop_id| vec![*workshop_id]).collect(),
+            };
+
+            for batch in batches {
+                let results = match self.run(&batch, validate).await {
+                    Ok(output) => parse_download_output(&output),
+                    Err(e) => {
+                        error!("{}", e);
+                        vec![]
+                    }
+                };
+
+                for workshop_id in &batch {
+                    let download = downloads.iter_mut().find(|d| d.workshop_id == *workshop_id).unwrap();
+                    download.attempts += 1;
+                    download.result = match results.iter().rev().find(|(id, _)| id == workshop_id) {
+                        Some((_, result)) => result.clone(),
+                        None => DownloadResult::Failed {
+                            reason: "no result in steamcmd output".to_owned(),
+                        },
+                    };
+
+                    if let DownloadResult::Downloaded { path, .. } = &download.result {
+                        let path = match path.is_dir() {
+                            true => Some(path.clone()),
+                            false => self.expected_content_dir(*workshop_id),
+                        };
+                        if !path.as_deref().map(content_dir_is_populated).unwrap_or(false) {
+                            download.result = DownloadResult::Failed {
+                                reason: "steamcmd reported success but the content folder is missing or empty"
+                                    .to_owned(),
+                            };
+                        }
+                    }
+                }
+            }
+
+            pending = downloads
+                .iter()
+                .filter(|d| matches!(d.result, DownloadResult::Failed { .. }))
+                .map(|d| d.workshop_id)
+                .collect();
+        }
+
+        for download in &downloads {
+            match &download.result {
+                DownloadResult::Downloaded { path, bytes } => info!(
+                    "Workshop item {} is downloaded to {} ({} bytes)",
+                    download.workshop_id,
+                    path.display(),
+                    bytes
+                ),
+                DownloadResult::Failed { reason } => error!(
+                    "Workshop item {} failed to download after {} attempts - {}",
+                    download.workshop_id, download.attempts, reason
+                ),
+            }
+        }
+
+        downloads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAKE_STEAMCMD: &str = r#"#!/bin/sh
+# Fails item 2 on its first download, succeeds for everything else.
+install_dir=""
+while [ $# -gt 0 ]; do
+    case "$1" in
+        +force_install_dir) install_dir="$2"; shift 2 ;;
+        +workshop_download_item)
+            id="$3"
+            dir="$install_dir/steamapps/workshop/content/108600/$id"
+            if [ "$id" = "2" ] && [ ! -f "$install_dir/failed_once" ]; then
+                touch "$install_dir/failed_once"
+                echo "ERROR! Download item $id failed (Failure)."
+            else
+                mkdir -p "$dir/mods" && touch "$dir/mods/mod.info"
+                echo "Success. Downloaded item $id to \"$dir\" (1024 bytes)"
+            fi
+            shift 3 ;;
+        *) shift ;;
+    esac
+done
+"#;
+
+    #[test]
+    fn steamcmd_output_parsing_test() {
+        let output = "Loading Steam API...OK\nSuccess. Downloaded item 1 to \"/tmp/a b/1\" (42 bytes)\nERROR! Download item 2 failed (Timeout).\n";
+        let results = parse_download_output(output);
+        assert_eq!(
+            results,
+            vec![
+                (
+                    1,
+                    DownloadResult::Downloaded {
+                        path: PathBuf::from("/tmp/a b/1"),
+                        bytes: 42
+                    }
+                ),
+                (
+                    2,
+                    DownloadResult::Failed {
+                        reason: "Timeout".to_owned()
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn steamcmd_fake_download_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_dir = std::env::temp_dir().join(format!("zso-steamcmd-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+
+        let script = test_dir.join("steamcmd.sh");
+        std::fs::write(&script, FAKE_STEAMCMD).unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let steamcmd = SteamCmd::new(SteamCmdSettings {
+            path: script,
+            install_dir: Some(test_dir.clone()),
+            login: "anonymous".to_owned(),
+            retries: 2,
+            retry_delay_sec: 0,
+        });
+
+        let downloads = steamcmd.download_items(&[1, 2], false).await;
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert_eq!(1, downloads[0].attempts);
+        assert_eq!(2, downloads[1].attempts);
+        assert!(downloads
+            .iter()
+            .all(|d| matches!(d.result, DownloadResult::Downloaded { bytes: 1024, .. })));
+    }
+}
@@ -0,0 +1,511 @@
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use log::{error, info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
+
+use crate::config::{NotifyEvent, RconSettings, SupervisorSettings, ZSOConfig};
+use crate::notify::{self, Notification};
+use crate::rcon::RconClient;
+
+pub const SERVER_LOG_NAME: &str = "server.log";
+
+/// `server.log` that moves to `server.log.1`, `.2`, ... once it grows past `max_bytes`.
+pub struct RotatingLog {
+    dir: PathBuf,
+    max_bytes: u64,
+    files: u32,
+    file: std::fs::File,
+    written: u64,
+}
+
+impl RotatingLog {
+    pub fn open(dir: &Path, max_bytes: u64, files: u32) -> Result<Self, String> {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            return Err(format!("failed to create {} - {}", dir.display(), e));
+        }
+
+        let path = dir.join(SERVER_LOG_NAME);
+        let file = match std::fs::OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => return Err(format!("failed to open {} - {}", path.display(), e)),
+        };
+        let written = file.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            files,
+            file,
+            written,
+        })
+    }
+
+    fn rotated_path(&self, index: u32) -> PathBuf {
+        self.dir.join(format!("{SERVER_LOG_NAME}.{index}"))
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let _ = std::fs::remove_file(self.rotated_path(self.files));
+        for index in (1..self.files).rev() {
+            let _ = std::fs::rename(self.rotated_path(index), self.rotated_path(index + 1));
+        }
+
+        let path = self.dir.join(SERVER_LOG_NAME);
+        match self.files {
+            0 => std::fs::remove_file(&path)?,
+            _ => std::fs::rename(&path, self.rotated_path(1))?,
+        }
+
+        self.file = std::fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        use std::io::Write;
+
+        if self.written > 0 && self.written + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Crash times inside the crash loop window.
+#[derive(Default, Debug)]
+struct CrashHistory {
+    crashes: Vec<Instant>,
+}
+
+impl CrashHistory {
+    /// Records a crash and returns how many happened within `window`, this one included.
+    fn record(&mut self, now: Instant, window: Duration) -> u32 {
+        self.crashes.retain(|crash| now.duration_since(*crash) < window);
+        self.crashes.push(now);
+        self.crashes.len() as u32
+    }
+}
+
+fn restart_delay(settings: &SupervisorSettings, recent_crashes: u32) -> Duration {
+    let factor = 2u64.saturating_pow(recent_crashes.saturating_sub(1));
+    Duration::from_secs(
+        settings
+            .restart_delay_sec
+            .saturating_mul(factor)
+            .min(settings.max_restart_delay_sec),
+    )
+}
+
+async fn forward_lines<R: AsyncRead + Unpin>(reader: R, prefix: &'static str, log_tx: mpsc::UnboundedSender<String>) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if log_tx.send(format!("{prefix}{line}")).is_err() {
+            return;
+        }
+    }
+}
+
+/// The process group a server runs in. start-server.sh starts java as its child instead of `exec`ing it,
+/// so signals have to go to the whole group. Whatever is left of the group is killed on drop.
+pub struct ProcessGroup {
+    pgid: Option<u32>,
+}
+
+impl ProcessGroup {
+    /// `child` must have been spawned with `process_group(0)`, its pid is the group id then.
+    pub fn of(child: &Child) -> Self {
+        Self { pgid: child.id() }
+    }
+
+    pub async fn signal(&self, signal: &str) {
+        if let Some(pgid) = self.pgid {
+            let _ = Command::new("kill")
+                .arg(format!("-{signal}"))
+                .arg("--")
+                .arg(format!("-{pgid}"))
+                .stderr(Stdio::null())
+                .status()
+                .await;
+        }
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.pgid {
+            let _ = std::process::Command::new("kill")
+                .arg("-KILL")
+                .arg("--")
+                .arg(format!("-{pgid}"))
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+}
+
+pub struct Supervisor {
+    settings: SupervisorSettings,
+    start_script: PathBuf,
+    rcon: Option<RconSettings>,
+    config: &'static ZSOConfig,
+}
+
+/// A running server and the stdin we keep open for it, the server stops reading commands on EOF.
+struct ServerProcess {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    group: ProcessGroup,
+}
+
+impl Supervisor {
+    pub fn new(settings: SupervisorSettings, install_dir: Option<&Path>, config: &'static ZSOConfig) -> Result<Self, String> {
+        let start_script = match (&settings.start_script, install_dir) {
+            (Some(start_script), _) => start_script.clone(),
+            (None, Some(install_dir)) => install_dir.join("start-server.sh"),
+            (None, None) => {
+                return Err("set supervisor.start_script or server_settings.install_dir".to_owned());
+            }
+        };
+
+        Ok(Self {
+            settings,
+            start_script,
+            rcon: config.rcon.clone(),
+            config,
+        })
+    }
+
+    fn spawn(&self, log_tx: &mpsc::UnboundedSender<String>) -> Result<ServerProcess, String> {
+        let mut command = Command::new(&self.start_script);
+        command
+            .args(&self.settings.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .kill_on_drop(true);
+
+        // start-server.sh uses paths relative to the install folder
+        if let Some(working_dir) = self.start_script.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            command.current_dir(working_dir);
+        }
+        if !self.settings.jvm_options.is_empty() {
+            command.env("JAVA_TOOL_OPTIONS", self.settings.jvm_options.join(" "));
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => return Err(format!("failed to start {} - {}", self.start_script.display(), e)),
+        };
+
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(forward_lines(stdout, "", log_tx.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(forward_lines(stderr, "[stderr] ", log_tx.clone()));
+        }
+        let stdin = child.stdin.take();
+        let group = ProcessGroup::of(&child);
+
+        info!("Server started with pid {}", child.id().unwrap_or_default());
+        Ok(ServerProcess { child, stdin, group })
+    }
+
+    async fn wait_for_exit(&self, child: &mut Child) -> Option<ExitStatus> {
+        let timeout = Duration::from_secs(self.settings.stop_timeout_sec);
+        tokio::time::timeout(timeout, child.wait()).await.ok().and_then(Result::ok)
+    }
+
+    /// `quit` over RCON (or the console when RCON isn't set up), then SIGTERM, then SIGKILL to the whole group.
+    async fn stop(&self, server: &mut ServerProcess) {
+        info!("Stopping server");
+
+        let quit_sent = match &self.rcon {
+            Some(rcon_settings) => match RconClient::connect(rcon_settings).await {
+                Ok(mut client) => client.exec("quit").await.is_ok(),
+                Err(e) => {
+                    warn!("Can't send quit over RCON - {}", e);
+                    false
+                }
+            },
+            None => match &mut server.stdin {
+                Some(stdin) => stdin.write_all(b"quit\n").await.is_ok(),
+                None => false,
+            },
+        };
+
+        if quit_sent {
+            if let Some(status) = self.wait_for_exit(&mut server.child).await {
+                info!("Server stopped ({})", status);
+                return;
+            }
+            warn!("Server didn't quit within {}s, sending SIGTERM", self.settings.stop_timeout_sec);
+        }
+
+        server.group.signal("TERM").await;
+        if let Some(status) = self.wait_for_exit(&mut server.child).await {
+            info!("Server stopped ({})", status);
+            return;
+        }
+        warn!("Server ignored SIGTERM for {}s, killing it", self.settings.stop_timeout_sec);
+
+        server.group.signal("KILL").await;
+        if let Err(e) = server.child.kill().await {
+            error!("Failed to kill the server - {}", e);
+        }
+    }
+
+    /// Keeps the server running until zso gets SIGTERM/SIGINT or the server is crash looping.
+    /// A clean exit, e.g. `quit` from an admin, restarts right away. One within `min_uptime_sec` counts as a crash.
+    pub async fn run(&self) -> Result<(), ()> {
+        let mut server_log = match RotatingLog::open(
+            &self.settings.log_dir,
+            self.settings.log_max_bytes,
+            self.settings.log_files,
+        ) {
+            Ok(server_log) => server_log,
+            Err(e) => {
+                error!("{}", e);
+                return Err(());
+            }
+        };
+        let (log_tx, mut log_rx) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            while let Some(line) = log_rx.recv().await {
+                if let Err(e) = server_log.write_line(&line) {
+                    error!("Failed to write server log - {}", e);
+                }
+            }
+        });
+
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(e) => {
+                error!("Failed to listen for SIGTERM - {}", e);
+                return Err(());
+            }
+        };
+
+        let crash_window = Duration::from_secs(self.settings.crash_loop_window_sec);
+        let min_uptime = Duration::from_secs(self.settings.min_uptime_sec);
+        let mut crash_history = CrashHistory::default();
+
+        loop {
+            let mut server = match self.spawn(&log_tx) {
+                Ok(server) => server,
+                Err(e) => {
+                    error!("{}", e);
+                    return Err(());
+                }
+            };
+            let started = Instant::now();
+
+            let status = tokio::select! {
+                status = server.child.wait() => status,
+                _ = terminate.recv() => {
+                    self.stop(&mut server).await;
+                    return Ok(());
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    self.stop(&mut server).await;
+                    return Ok(());
+                }
+            };
+
+            let delay = match status {
+                Ok(status) if status.success() && started.elapsed() >= min_uptime => {
+                    info!("Server exited cleanly, restarting");
+                    Duration::ZERO
+                }
+                Ok(status) => {
+                    let status = match status.success() {
+                        true => format!("{status} right after starting"),
+                        false => status.to_string(),
+                    };
+                    let recent_crashes = crash_history.record(Instant::now(), crash_window);
+                    error!(
+                        "Server crashed ({}), {} crashes in the last {}s",
+                        status, recent_crashes, self.settings.crash_loop_window_sec
+                    );
+                    let giving_up = recent_crashes >= self.settings.crash_loop_count;
+                    notify::notify_in_background(
+                        self.config,
+                        Notification::new(
+                            NotifyEvent::Crash,
+                            "Server crashed",
+                            match giving_up {
+                                true => format!("{status}, crash looping - not restarting it again"),
+                                false => format!("{status}, {recent_crashes} crashes in the last {}s", self.settings.crash_loop_window_sec),
+                            },
+                        ),
+                    );
+                    if giving_up {
+                        error!("Server is crash looping - giving up, check {}", self.settings.log_dir.display());
+                        return Err(());
+                    }
+                    restart_delay(&self.settings, recent_crashes)
+                }
+                Err(e) => {
+                    error!("Failed to wait for the server - {}", e);
+                    return Err(());
+                }
+            };
+
+            if !delay.is_zero() {
+                info!("Restarting server in {}s", delay.as_secs());
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = terminate.recv() => return Ok(()),
+                _ = tokio::signal::ctrl_c() => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotating_log_test() {
+        let test_dir = std::env::temp_dir().join(format!("zso-log-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        let mut server_log = RotatingLog::open(&test_dir, 10, 2).unwrap();
+        for line in ["first", "second", "third", "fourth"] {
+            server_log.write_line(line).unwrap();
+        }
+
+        let read = |name: &str| std::fs::read_to_string(test_dir.join(name)).unwrap_or_default();
+        let (current, rotated_1, rotated_2) = (read("server.log"), read("server.log.1"), read("server.log.2"));
+        let rotated_3_exists = test_dir.join("server.log.3").exists();
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert_eq!("fourth\n", current);
+        assert_eq!("third\n", rotated_1);
+        assert_eq!("second\n", rotated_2);
+        assert!(!rotated_3_exists);
+    }
+
+    #[test]
+    fn crash_backoff_test() {
+        let settings = SupervisorSettings {
+            restart_delay_sec: 5,
+            max_restart_delay_sec: 30,
+            ..Default::default()
+        };
+        let window = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut crash_history = CrashHistory::default();
+
+        assert_eq!(1, crash_history.record(start, window));
+        assert_eq!(2, crash_history.record(start + Duration::from_secs(10), window));
+        assert_eq!(Duration::from_secs(10), restart_delay(&settings, 2));
+        assert_eq!(Duration::from_secs(30), restart_delay(&settings, 5));
+        // the first two crashes fell out of the window
+        assert_eq!(1, crash_history.record(start + Duration::from_secs(100), window));
+    }
+
+    /// Like start-server.sh: the "server" runs as a child of the script, not in its place.
+    fn fake_start_script(test_dir: &Path) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let start_script = test_dir.join("start-server.sh");
+        std::fs::create_dir_all(test_dir).unwrap();
+        std::fs::write(&start_script, "#!/bin/sh\nsleep 300 &\necho $! > server.pid\nwait\n").unwrap();
+        std::fs::set_permissions(&start_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        start_script
+    }
+
+    async fn server_pid(test_dir: &Path) -> u32 {
+        for _ in 0..50 {
+            if let Ok(pid) = std::fs::read_to_string(test_dir.join("server.pid")) {
+                if let Ok(pid) = pid.trim().parse() {
+                    return pid;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("the fake server didn't start");
+    }
+
+    /// Zombies count as gone, nobody might be reaping orphans in a container.
+    async fn is_running(pid: u32) -> bool {
+        for _ in 0..50 {
+            let running = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+                .map(|stat| stat.rsplit_once(')').map(|(_, rest)| rest.trim_start().chars().next()) != Some(Some('Z')))
+                .unwrap_or(false);
+            if !running {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
+
+    #[tokio::test]
+    async fn stop_kills_process_group_test() {
+        let test_dir = std::env::temp_dir().join(format!("zso-supervisor-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        let settings = SupervisorSettings {
+            start_script: Some(fake_start_script(&test_dir)),
+            stop_timeout_sec: 1,
+            ..Default::default()
+        };
+        let supervisor = Supervisor::new(settings, None, Box::leak(Box::default())).unwrap();
+        let (log_tx, _log_rx) = mpsc::unbounded_channel();
+
+        let mut server = supervisor.spawn(&log_tx).unwrap();
+        let stopped_pid = server_pid(&test_dir).await;
+        supervisor.stop(&mut server).await;
+        let stopped = !is_running(stopped_pid).await;
+
+        let _ = std::fs::remove_file(test_dir.join("server.pid"));
+        let server = supervisor.spawn(&log_tx).unwrap();
+        let dropped_pid = server_pid(&test_dir).await;
+        drop(server);
+        let dropped = !is_running(dropped_pid).await;
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert!(stopped, "stop left the server's child running");
+        assert!(dropped, "dropping the server left its child running");
+    }
+
+    #[tokio::test]
+    async fn quick_clean_exits_count_as_crashes_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let test_dir = std::env::temp_dir().join(format!("zso-supervisor-exit-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&test_dir);
+        std::fs::create_dir_all(&test_dir).unwrap();
+        let start_script = test_dir.join("start-server.sh");
+        std::fs::write(&start_script, "#!/bin/sh\necho started >> runs\nexit 0\n").unwrap();
+        std::fs::set_permissions(&start_script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let settings = SupervisorSettings {
+            start_script: Some(start_script),
+            log_dir: test_dir.join("logs"),
+            crash_loop_count: 3,
+            crash_loop_window_sec: 600,
+            min_uptime_sec: 60,
+            ..Default::default()
+        };
+        let supervisor = Supervisor::new(settings, None, Box::leak(Box::default())).unwrap();
+
+        let outcome = tokio::time::timeout(Duration::from_secs(10), supervisor.run()).await;
+        let runs = std::fs::read_to_string(test_dir.join("runs")).unwrap_or_default();
+        let _ = std::fs::remove_dir_all(&test_dir);
+
+        assert_eq!(Ok(Err(())), outcome, "a server that exits right away must trip crash loop detection");
+        assert_eq!(3, runs.lines().count());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+/// Value a template can reference. Lists render joined with `;` unless a `join` filter is given.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateValue {
+    Text(String),
+    List(Vec<TemplateValue>),
+    Map(BTreeMap<String, TemplateValue>),
+}
+
+impl TemplateValue {
+    pub fn text(value: impl std::fmt::Display) -> Self {
+        TemplateValue::Text(value.to_string())
+    }
+
+    pub fn list<T: std::fmt::Display>(values: &[T]) -> Self {
+        TemplateValue::List(values.iter().map(TemplateValue::text).collect())
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            TemplateValue::Text(text) => !text.is_empty() && text != "false" && text != "0",
+            TemplateValue::List(list) => !list.is_empty(),
+            TemplateValue::Map(map) => !map.is_empty(),
+        }
+    }
+
+    pub fn render(&self, separator: &str) -> String {
+        match self {
+            TemplateValue::Text(text) => text.clone(),
+            TemplateValue::List(list) => list
+                .iter()
+                .map(|value| value.render(separator))
+                .collect::<Vec<String>>()
+                .join(separator),
+            TemplateValue::Map(_) => String::new(),
+        }
+    }
+}
+
+pub type TemplateContext = BTreeMap<String, TemplateValue>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Value { path: String, separator: String },
+    Each { path: String, body: Vec<Node> },
+    If { path: String, then: Vec<Node>, otherwise: Vec<Node> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Block {
+    Each,
+    If,
+}
+
+/// Block being parsed. The root frame has no block and is never popped by a closing tag.
+#[derive(Default)]
+struct Frame {
+    block: Option<(Block, String, usize)>,
+    nodes: Vec<Node>,
+    /// `then` branch of an `if` once its `else` was seen.
+    then: Option<Vec<Node>>,
+}
+
+impl Frame {
+    fn open(block: Block, path: &str, offset: usize) -> Self {
+        Self {
+            block: Some((block, path.trim().to_owned(), offset)),
+            ..Default::default()
+        }
+    }
+}
+
+/// A tiny handlebars-like template:
+/// `{{ name }}`, `{{ list | join ", " }}`, `{{#each list}}...{{/each}}`, `{{#if name}}...{{else}}...{{/if}}`.
+/// Inside `each`, fields of the current item are in scope and `{{ this }}` is the item itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut tags: Vec<(usize, &str)> = vec![];
+        let mut rest = source;
+        let mut offset = 0;
+        let mut texts: Vec<&str> = vec![];
+
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => return Err(format!("unclosed tag at {}", offset + start)),
+            };
+            texts.push(&rest[..start]);
+            tags.push((offset + start, rest[start + 2..end].trim()));
+            offset += end + 2;
+            rest = &rest[end + 2..];
+        }
+        texts.push(rest);
+
+        let mut stack: Vec<Frame> = vec![Frame::default()];
+
+        for (pos, text) in texts.iter().enumerate() {
+            if !text.is_empty() {
+                stack.last_mut().unwrap().nodes.push(Node::Text(text.to_string()));
+            }
+
+            let (tag_offset, tag) = match tags.get(pos) {
+                Some(tag) => *tag,
+                None => break,
+            };
+
+            if let Some(path) = tag.strip_prefix("#each ") {
+                stack.push(Frame::open(Block::Each, path, tag_offset));
+            } else if let Some(path) = tag.strip_prefix("#if ") {
+                stack.push(Frame::open(Block::If, path, tag_offset));
+            } else if tag == "else" {
+                let frame = stack.last_mut().unwrap();
+                match (&frame.block, &frame.then) {
+                    (Some((Block::If, _, _)), None) => frame.then = Some(std::mem::take(&mut frame.nodes)),
+                    _ => return Err(format!("unexpected else at {tag_offset}")),
+                }
+            } else if tag == "/each" || tag == "/if" {
+                let frame = stack.pop().unwrap();
+                let node = match (tag, frame.block) {
+                    ("/each", Some((Block::Each, path, _))) => Node::Each { path, body: frame.nodes },
+                    ("/if", Some((Block::If, path, _))) => match frame.then {
+                        Some(then) => Node::If { path, then, otherwise: frame.nodes },
+                        None => Node::If { path, then: frame.nodes, otherwise: vec![] },
+                    },
+                    (_, Some((_, _, open_offset))) => {
+                        return Err(format!("{tag} at {tag_offset} doesn't match the block opened at {open_offset}"))
+                    }
+                    (_, None) => return Err(format!("unexpected {tag} at {tag_offset}")),
+                };
+                stack.last_mut().unwrap().nodes.push(node);
+            } else {
+                let (path, separator) = match tag.split_once('|') {
+                    Some((path, filter)) => {
+                        let filter = filter.trim();
+                        let separator = match filter.strip_prefix("join") {
+                            Some(separator) => separator.trim().trim_matches('"').to_owned(),
+                            None => return Err(format!("unknown filter '{filter}' at {tag_offset}")),
+                        };
+                        (path.trim(), separator)
+                    }
+                    None => (tag, ";".to_owned()),
+                };
+                stack.last_mut().unwrap().nodes.push(Node::Value {
+                    path: path.to_owned(),
+                    separator,
+                });
+            }
+        }
+
+        let frame = stack.pop().unwrap();
+        match frame.block {
+            None => Ok(Self { nodes: frame.nodes }),
+            Some((_, _, open_offset)) => Err(format!("block opened at {open_offset} is never closed")),
+        }
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> String {
+        let mut output = String::new();
+        render_nodes(&self.nodes, &[context], None, &mut output);
+        output
+    }
+}
+
+fn lookup<'a>(path: &str, scopes: &[&'a TemplateContext], this: Option<&'a TemplateValue>) -> Option<&'a TemplateValue> {
+    if path == "this" {
+        return this;
+    }
+
+    let mut parts = path.split('.');
+    let first = parts.next()?;
+    let mut value = scopes.iter().rev().find_map(|scope| scope.get(first))?;
+
+    for part in parts {
+        value = match value {
+            TemplateValue::Map(map) => map.get(part)?,
+            _ => return None,
+        };
+    }
+
+    Some(value)
+}
+
+fn render_nodes(nodes: &[Node], scopes: &[&TemplateContext], this: Option<&TemplateValue>, output: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Value { path, separator } => {
+                if let Some(value) = lookup(path, scopes, this) {
+                    output.push_str(&value.render(separator));
+                }
+            }
+            Node::Each { path, body } => {
+                let items = match lookup(path, scopes, this) {
+                    Some(TemplateValue::List(items)) => items,
+                    _ => continue,
+                };
+                for item in items {
+                    let mut item_scopes = scopes.to_vec();
+                    if let TemplateValue::Map(fields) = item {
+                        item_scopes.push(fields);
+                    }
+                    render_nodes(body, &item_scopes, Some(item), output);
+                }
+            }
+            Node::If { path, then, otherwise } => {
+                let truthy = lookup(path, scopes, this).map(TemplateValue::is_truthy).unwrap_or(false);
+                match truthy {
+                    true => render_nodes(then, scopes, this, output),
+                    false => render_nodes(otherwise, scopes, this, output),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_render_test() {
+        let mut item = BTreeMap::new();
+        item.insert("title".to_owned(), TemplateValue::text("Brita"));
+        item.insert("mod_ids".to_owned(), TemplateValue::list(&["Brita", "Arsenal"]));
+
+        let mut context = TemplateContext::new();
+        context.insert("mod_ids".to_owned(), TemplateValue::list(&["a", "b"]));
+        context.insert("items".to_owned(), TemplateValue::List(vec![TemplateValue::Map(item)]));
+        context.insert("empty".to_owned(), TemplateValue::list::<String>(&[]));
+
+        let template = Template::parse(
+            "MODS={{ mod_ids }}\n{{#each items}}- {{ title }}: {{ mod_ids | join \", \" }}\n{{/each}}{{#if empty}}yes{{else}}no{{/if}}",
+        )
+        .unwrap();
+
+        assert_eq!("MODS=a;b\n- Brita: Brita, Arsenal\nno", template.render(&context));
+    }
+
+    #[test]
+    fn template_unbalanced_test() {
+        assert!(Template::parse("{{#each items}}").is_err());
+        assert!(Template::parse("{{/if}}").is_err());
+        assert!(Template::parse("{{#if a}}{{/each}}").is_err());
+    }
+}
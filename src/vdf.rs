@@ -0,0 +1,151 @@
+/// Valve KeyValues (VDF) text format, as used by `.acf` manifests.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VdfValue {
+    String(String),
+    Object(Vec<(String, VdfValue)>),
+}
+
+impl VdfValue {
+    /// Keys are case-insensitive, steam isn't consistent about them.
+    pub fn get(&self, key: &str) -> Option<&VdfValue> {
+        match self {
+            VdfValue::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value),
+            VdfValue::String(_) => None,
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(VdfValue::as_str)
+    }
+
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get_str(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            VdfValue::String(value) => Some(value),
+            VdfValue::Object(_) => None,
+        }
+    }
+
+    pub fn entries(&self) -> &[(String, VdfValue)] {
+        match self {
+            VdfValue::Object(entries) => entries,
+            VdfValue::String(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Text(String),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '/' if matches!(chars.peek(), Some((_, '/'))) => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            // platform conditionals like [$WIN32] are ignored
+            '[' => {
+                for (_, c) in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, 't')) => value.push('\t'),
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(format!("unterminated string at {pos}")),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(format!("unterminated string at {pos}")),
+                    }
+                }
+                tokens.push(Token::Text(value));
+            }
+            c => {
+                let mut value = String::from(c);
+                while let Some((_, next)) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, '{' | '}' | '"') {
+                        break;
+                    }
+                    value.push(*next);
+                    chars.next();
+                }
+                tokens.push(Token::Text(value));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_object(tokens: &mut std::vec::IntoIter<Token>, nested: bool) -> Result<VdfValue, String> {
+    let mut entries = vec![];
+
+    loop {
+        let key = match tokens.next() {
+            Some(Token::Text(key)) => key,
+            Some(Token::Close) if nested => return Ok(VdfValue::Object(entries)),
+            None if !nested => return Ok(VdfValue::Object(entries)),
+            Some(token) => return Err(format!("expected a key, got {:?}", token)),
+            None => return Err("unexpected end of file, missing }".to_owned()),
+        };
+
+        let value = match tokens.next() {
+            Some(Token::Text(value)) => VdfValue::String(value),
+            Some(Token::Open) => parse_object(tokens, true)?,
+            Some(Token::Close) | None => return Err(format!("key {key} has no value")),
+        };
+
+        entries.push((key, value));
+    }
+}
+
+/// Parses a whole file into an object holding its top level keys.
+pub fn parse(text: &str) -> Result<VdfValue, String> {
+    let mut tokens = tokenize(text)?.into_iter();
+    parse_object(&mut tokens, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vdf_parse_test() {
+        let text = "// comment\n\"Root\"\n{\n\t\"Name\"\t\t\"a \\\"quoted\\\" value\"\n\tbare value [$WIN32]\n\t\"Child\" { \"key\" \"1\" }\n}\n";
+        let root = parse(text).unwrap();
+        let root = root.get("root").unwrap();
+
+        assert_eq!(Some("a \"quoted\" value"), root.get_str("name"));
+        assert_eq!(Some("value"), root.get_str("bare"));
+        assert_eq!(Some(1), root.get("Child").unwrap().get_u64("key"));
+        assert!(parse("\"Root\" { \"key\" ").is_err());
+    }
+}
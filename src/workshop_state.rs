@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::error;
+
+use crate::config::ZSOConfig;
+use crate::steam_api_client::ModData;
+use crate::steamcmd::ZOMBOID_APP_ID;
+use crate::vdf;
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct InstalledItem {
+    pub size: u64,
+    pub time_updated: u64,
+    pub manifest: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ItemDetails {
+    pub manifest: String,
+    pub time_updated: u64,
+    pub latest_manifest: Option<String>,
+    pub latest_time_updated: Option<u64>,
+}
+
+/// `steamapps/workshop/appworkshop_108600.acf`: what steam has downloaded for the game.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct AppWorkshop {
+    pub installed: BTreeMap<u64, InstalledItem>,
+    pub details: BTreeMap<u64, ItemDetails>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaleReason {
+    NotDownloaded,
+    Outdated { installed: u64, latest: u64 },
+    ManifestChanged { installed: String, latest: String },
+}
+
+impl std::fmt::Display for StaleReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StaleReason::NotDownloaded => write!(f, "not downloaded"),
+            StaleReason::Outdated { installed, latest } => {
+                write!(f, "installed version from {installed}, workshop has {latest}")
+            }
+            StaleReason::ManifestChanged { installed, latest } => {
+                write!(f, "installed manifest {installed}, latest manifest {latest}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaleItem {
+    pub workshop_id: u64,
+    pub title: String,
+    pub reason: StaleReason,
+}
+
+pub fn parse_appworkshop(text: &str) -> Result<AppWorkshop, String> {
+    let root = vdf::parse(text)?;
+    let app_workshop = match root.get("AppWorkshop") {
+        Some(app_workshop) => app_workshop,
+        None => return Err("AppWorkshop section is missing".to_owned()),
+    };
+
+    let mut parsed = AppWorkshop::default();
+
+    if let Some(installed) = app_workshop.get("WorkshopItemsInstalled") {
+        for (workshop_id, item) in installed.entries() {
+            let workshop_id = match workshop_id.parse::<u64>() {
+                Ok(workshop_id) => workshop_id,
+                Err(_) => continue,
+            };
+            parsed.installed.insert(
+                workshop_id,
+                InstalledItem {
+                    size: item.get_u64("size").unwrap_or_default(),
+                    time_updated: item.get_u64("timeupdated").unwrap_or_default(),
+                    manifest: item.get_str("manifest").unwrap_or_default().to_owned(),
+                },
+            );
+        }
+    }
+
+    if let Some(details) = app_workshop.get("WorkshopItemDetails") {
+        for (workshop_id, item) in details.entries() {
+            let workshop_id = match workshop_id.parse::<u64>() {
+                Ok(workshop_id) => workshop_id,
+                Err(_) => continue,
+            };
+            parsed.details.insert(
+                workshop_id,
+                ItemDetails {
+                    manifest: item.get_str("manifest").unwrap_or_default().to_owned(),
+                    time_updated: item.get_u64("timeupdated").unwrap_or_default(),
+                    latest_manifest: item.get_str("latest_manifest").map(str::to_owned),
+                    latest_time_updated: item.get_u64("latest_timeupdated"),
+                },
+            );
+        }
+    }
+
+    Ok(parsed)
+}
+
+pub async fn load_appworkshop(path: &Path) -> Result<AppWorkshop, ()> {
+    let acf_data = match tokio::fs::read_to_string(path).await {
+        Ok(acf_data) => acf_data,
+        Err(e) => {
+            error!("Failed to open {} - {}", path.display(), e);
+            return Err(());
+        }
+    };
+
+    match parse_appworkshop(&acf_data) {
+        Ok(app_workshop) => Ok(app_workshop),
+        Err(e) => {
+            error!("Failed to parse {} - {}", path.display(), e);
+            Err(())
+        }
+    }
+}
+
+/// Finds the acf next to the downloaded content, or below the steamcmd install dir.
+pub fn appworkshop_path(config: &ZSOConfig) -> Option<PathBuf> {
+    let file_name = format!("appworkshop_{ZOMBOID_APP_ID}.acf");
+
+    if let Some(content_dir) = &config.workshop_settings.content_dir {
+        // content_dir is steamapps/workshop/content/108600
+        if let Some(workshop_dir) = content_dir.parent().and_then(Path::parent) {
+            return Some(workshop_dir.join(file_name));
+        }
+    }
+
+    config
+        .steamcmd
+        .as_ref()
+        .and_then(|steamcmd| steamcmd.install_dir.as_ref())
+        .map(|install_dir| install_dir.join("steamapps/workshop").join(file_name))
+}
+
+/// Compares what is on disk with what the workshop reports.
+pub fn find_stale_items(app_workshop: &AppWorkshop, mods_data: &[ModData]) -> Vec<StaleItem> {
+    let mut stale: Vec<StaleItem> = vec![];
+
+    for mod_data in mods_data {
+        if stale.iter().any(|item| item.workshop_id == mod_data.mod_id) {
+            continue;
+        }
+
+        let reason = match app_workshop.installed.get(&mod_data.mod_id) {
+            None => Some(StaleReason::NotDownloaded),
+            Some(installed) if installed.time_updated < mod_data.last_updated => Some(StaleReason::Outdated {
+                installed: installed.time_updated,
+                latest: mod_data.last_updated,
+            }),
+            Some(installed) => match app_workshop
+                .details
+                .get(&mod_data.mod_id)
+                .and_then(|details| details.latest_manifest.as_ref())
+            {
+                Some(latest) if !latest.is_empty() && *latest != installed.manifest => {
+                    Some(StaleReason::ManifestChanged {
+                        installed: installed.manifest.clone(),
+                        latest: latest.clone(),
+                    })
+                }
+                _ => None,
+            },
+        };
+
+        if let Some(reason) = reason {
+            stale.push(StaleItem {
+                workshop_id: mod_data.mod_id,
+                title: mod_data.title.clone(),
+                reason,
+            });
+        }
+    }
+
+    stale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APPWORKSHOP: &str = r#"
+"AppWorkshop"
+{
+	"appid"		"108600"
+	"SizeOnDisk"		"3072"
+	"WorkshopItemsInstalled"
+	{
+		"1"
+		{
+			"size"		"1024"
+			"timeupdated"		"1700000000"
+			"manifest"		"111"
+		}
+		"2"
+		{
+			"size"		"1024"
+			"timeupdated"		"1600000000"
+			"manifest"		"222"
+		}
+		"3"
+		{
+			"size"		"1024"
+			"timeupdated"		"1700000000"
+			"manifest"		"333"
+		}
+	}
+	"WorkshopItemDetails"
+	{
+		"3"
+		{
+			"manifest"		"333"
+			"timeupdated"		"1700000000"
+			"timetouched"		"1700000001"
+			"latest_timeupdated"		"1700000500"
+			"latest_manifest"		"334"
+		}
+	}
+}
+"#;
+
+    fn mod_data(mod_id: u64, last_updated: u64) -> ModData {
+        ModData {
+            mod_id,
+            last_updated,
+            title: format!("Item {mod_id}"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stale_items_test() {
+        let app_workshop = parse_appworkshop(APPWORKSHOP).unwrap();
+        assert_eq!(3, app_workshop.installed.len());
+        assert_eq!(Some("334".to_owned()), app_workshop.details[&3].latest_manifest);
+
+        let mods_data = vec![
+            mod_data(1, 1700000000),
+            mod_data(2, 1650000000),
+            mod_data(3, 1700000000),
+            mod_data(4, 1700000000),
+        ];
+        let stale = find_stale_items(&app_workshop, &mods_data);
+
+        assert_eq!(
+            vec![
+                StaleReason::Outdated {
+                    installed: 1600000000,
+                    latest: 1650000000
+                },
+                StaleReason::ManifestChanged {
+                    installed: "333".to_owned(),
+                    latest: "334".to_owned()
+                },
+                StaleReason::NotDownloaded,
+            ],
+            stale.into_iter().map(|item| item.reason).collect::<Vec<StaleReason>>()
+        );
+    }
+}
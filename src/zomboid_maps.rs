@@ -0,0 +1,322 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use log::{debug, info, warn};
+
+use crate::steam_api_client::ModData;
+
+pub const VANILLA_MAP: &str = "Muldraugh, KY";
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct MapInfo {
+    pub title: Option<String>,
+    pub lots: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct MapCoverage {
+    /// `None` for maps shipped with the game.
+    pub workshop_id: Option<u64>,
+    pub folder: String,
+    pub info: MapInfo,
+    pub cells: BTreeSet<(i32, i32)>,
+}
+
+impl MapCoverage {
+    pub fn describe(&self) -> String {
+        match self.workshop_id {
+            Some(workshop_id) => format!("{} ({})", self.folder, workshop_id),
+            None => format!("{} (vanilla)", self.folder),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellOverlap {
+    pub first: MapCoverage,
+    pub second: MapCoverage,
+    pub cells: Vec<(i32, i32)>,
+}
+
+/// The version folder a map path sits in, like `42` or `42.0.1`, relative to its workshop item.
+/// Empty for maps outside one.
+fn build_folder_version(map_dir: &Path) -> Vec<u32> {
+    map_dir
+        .components()
+        .take_while(|component| component.as_os_str() != "media")
+        .filter_map(|component| {
+            component
+                .as_os_str()
+                .to_str()?
+                .split('.')
+                .map(|part| part.parse::<u32>().ok())
+                .collect::<Option<Vec<u32>>>()
+        })
+        .last()
+        .unwrap_or_default()
+}
+
+/// Looks for `media/maps/<map_folder>` anywhere inside a downloaded workshop item.
+/// Workshop items keep maps under `mods/<mod>/media/maps`, B42 items add a version folder in between.
+/// Sorted by version folder, the last one is what the newest game build loads.
+pub(crate) fn find_map_dirs(content_dir: &Path, workshop_id: u64, map_folder: &str) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = vec![];
+    let item_dir = content_dir.join(workshop_id.to_string());
+    let mut to_visit: Vec<PathBuf> = vec![item_dir.clone()];
+
+    while let Some(dir) = to_visit.pop() {
+        let candidate = dir.join("media").join("maps").join(map_folder);
+        // B41 and B42 folders can sit next to each other, so keep looking below a match
+        if candidate.is_dir() {
+            found.push(candidate);
+        }
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() && entry.file_name() != "media" {
+                to_visit.push(path);
+            }
+        }
+    }
+
+    found.sort_by_cached_key(|path| (build_folder_version(path.strip_prefix(&item_dir).unwrap_or(path)), path.clone()));
+    found
+}
+
+pub(crate) fn parse_map_info(map_info: &str) -> MapInfo {
+    let mut info = MapInfo::default();
+
+    for line in map_info.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some(pair) => pair,
+            None => continue,
+        };
+
+        match key.trim() {
+            "title" => info.title = Some(value.trim().to_owned()),
+            "lots" => info.lots.push(value.trim().to_owned()),
+            _ => {}
+        }
+    }
+
+    info
+}
+
+/// Parses a `<x>_<y>.lotheader` file name into cell coordinates.
+pub(crate) fn parse_lotheader_name(file_name: &str) -> Option<(i32, i32)> {
+    let stem = file_name.strip_suffix(".lotheader")?;
+    let (x, y) = stem.split_once('_')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+pub(crate) fn read_map_coverage(map_dir: &Path, folder: &str, workshop_id: Option<u64>) -> MapCoverage {
+    let mut coverage = MapCoverage {
+        workshop_id,
+        folder: folder.to_owned(),
+        ..Default::default()
+    };
+
+    match std::fs::read_to_string(map_dir.join("map.info")) {
+        Ok(map_info) => coverage.info = parse_map_info(&map_info),
+        Err(e) => debug!("No map.info in {}: {}", map_dir.display(), e),
+    }
+
+    let entries = match std::fs::read_dir(map_dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read map folder {} - {}", map_dir.display(), e);
+            return coverage;
+        }
+    };
+
+    for entry in entries.flatten() {
+        if let Some(cell) = parse_lotheader_name(&entry.file_name().to_string_lossy()) {
+            coverage.cells.insert(cell);
+        }
+    }
+
+    coverage
+}
+
+/// Builds cell coverage for every map folder declared by the resolved workshop items.
+pub(crate) fn collect_map_coverage(
+    mods_data: &[ModData],
+    content_dir: &Path,
+    maps_to_exclude: &[String],
+) -> Vec<MapCoverage> {
+    let mut coverages: Vec<MapCoverage> = vec![];
+
+    for mod_data in mods_data {
+        for map_name in &mod_data.map_name {
+            if maps_to_exclude.contains(map_name) {
+                continue;
+            }
+
+            let map_dirs = find_map_dirs(content_dir, mod_data.mod_id, map_name);
+            let map_dir = match map_dirs.last() {
+                Some(map_dir) => map_dir,
+                None => {
+                    warn!(
+                        "Map folder {} of workshop item {} is not downloaded in {}",
+                        map_name,
+                        mod_data.mod_id,
+                        content_dir.display()
+                    );
+                    continue;
+                }
+            };
+
+            let coverage = read_map_coverage(map_dir, map_name, Some(mod_data.mod_id));
+            debug!("Map {} covers {} cells", coverage.describe(), coverage.cells.len());
+            coverages.push(coverage);
+        }
+    }
+
+    coverages
+}
+
+pub(crate) fn find_overlaps(coverages: &[MapCoverage]) -> Vec<CellOverlap> {
+    let mut overlaps: Vec<CellOverlap> = vec![];
+
+    for (pos, first) in coverages.iter().enumerate() {
+        for second in &coverages[pos + 1..] {
+            let cells = first
+                .cells
+                .intersection(&second.cells)
+                .copied()
+                .collect::<Vec<(i32, i32)>>();
+
+            if !cells.is_empty() {
+                overlaps.push(CellOverlap {
+                    first: first.clone(),
+                    second: second.clone(),
+                    cells,
+                });
+            }
+        }
+    }
+
+    overlaps
+}
+
+pub(crate) fn report_overlaps(overlaps: &[CellOverlap]) {
+    if overlaps.is_empty() {
+        info!("No map cell overlaps found");
+        return;
+    }
+
+    for overlap in overlaps {
+        let sample = overlap
+            .cells
+            .iter()
+            .take(5)
+            .map(|(x, y)| format!("{x}_{y}"))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        warn!(
+            "Maps {} and {} overlap on {} cells: {}{}",
+            overlap.first.describe(),
+            overlap.second.describe(),
+            overlap.cells.len(),
+            sample,
+            if overlap.cells.len() > 5 { ", ..." } else { "" }
+        );
+    }
+}
+
+/// Reports cell overlaps between map mods and against the vanilla map, if the server install is known.
+pub(crate) fn check_map_overlaps(
+    mods_data: &[ModData],
+    content_dir: &Path,
+    install_dir: Option<&Path>,
+    maps_to_exclude: &[String],
+) -> Vec<CellOverlap> {
+    let mut coverages = vec![];
+
+    match install_dir {
+        Some(install_dir) => {
+            let vanilla_dir = install_dir.join("media").join("maps").join(VANILLA_MAP);
+            coverages.push(read_map_coverage(&vanilla_dir, VANILLA_MAP, None));
+        }
+        None => {
+            info!("server_settings.install_dir is not set - skipping overlap check against the vanilla map");
+        }
+    }
+
+    coverages.extend(collect_map_coverage(mods_data, content_dir, maps_to_exclude));
+
+    let overlaps = find_overlaps(&coverages);
+    report_overlaps(&overlaps);
+    overlaps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(folder: &str, cells: &[(i32, i32)]) -> MapCoverage {
+        MapCoverage {
+            workshop_id: Some(1),
+            folder: folder.to_owned(),
+            cells: cells.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_lotheader_name_test() {
+        assert_eq!(Some((35, 32)), parse_lotheader_name("35_32.lotheader"));
+        assert_eq!(Some((-1, 4)), parse_lotheader_name("-1_4.lotheader"));
+        assert_eq!(None, parse_lotheader_name("35_32.lotpack"));
+        assert_eq!(None, parse_lotheader_name("world_0_0.lotpack"));
+        assert_eq!(None, parse_lotheader_name("35.lotheader"));
+    }
+
+    #[test]
+    fn parse_map_info_test() {
+        let info = parse_map_info("title=Bedford Falls\nlots=Muldraugh, KY\nlots = Riverside, KY\ndescription=A town\n");
+
+        assert_eq!(Some("Bedford Falls".to_owned()), info.title);
+        assert_eq!(vec!["Muldraugh, KY", "Riverside, KY"], info.lots);
+        assert_eq!(MapInfo::default(), parse_map_info("no pairs here"));
+    }
+
+    #[test]
+    fn find_overlaps_test() {
+        let coverages = [
+            coverage("Bedford Falls", &[(1, 1), (1, 2), (2, 2)]),
+            coverage("Raven Creek", &[(5, 5)]),
+            coverage("Bedford Falls Extended", &[(2, 2), (1, 2), (3, 3)]),
+        ];
+
+        let overlaps = find_overlaps(&coverages);
+        assert_eq!(1, overlaps.len());
+        assert_eq!("Bedford Falls", overlaps[0].first.folder);
+        assert_eq!("Bedford Falls Extended", overlaps[0].second.folder);
+        assert_eq!(vec![(1, 2), (2, 2)], overlaps[0].cells);
+    }
+
+    #[test]
+    fn find_map_dirs_test() {
+        let content_dir = std::env::temp_dir().join(format!("zso-map-dirs-{}", std::process::id()));
+        let item_dir = content_dir.join("123").join("mods").join("Bedford");
+        for build in ["", "42.0", "common", "42.10", "41"] {
+            std::fs::create_dir_all(item_dir.join(build).join("media").join("maps").join("Bedford Falls")).unwrap();
+        }
+
+        let map_dirs = find_map_dirs(&content_dir, 123, "Bedford Falls");
+        let _ = std::fs::remove_dir_all(&content_dir);
+
+        let builds = map_dirs
+            .iter()
+            .map(|map_dir| map_dir.strip_prefix(&item_dir).unwrap().components().next().unwrap().as_os_str().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["common", "media", "41", "42.0", "42.10"], builds);
+    }
+}
@@ -94,7 +94,15 @@ pub(crate) fn generate_map_string(
     map_string
 }
 
-pub fn ini_initial_check(ini_path: &PathBuf) {
+/// Lists currently set in the server ini, empty entries are dropped.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct IniModLists {
+    pub workshop_items: Vec<String>,
+    pub mods: Vec<String>,
+    pub maps: Vec<String>,
+}
+
+pub fn ini_initial_check(ini_path: &PathBuf) -> IniModLists {
     let server_conf = match Ini::load_from_file(ini_path) {
         Ok(server_conf) => server_conf,
         Err(e) => {
@@ -151,7 +159,20 @@ pub fn ini_initial_check(ini_path: &PathBuf) {
         current_workshop_items.len(),
         current_mods.len(),
         current_maps.len()
-    )
+    );
+
+    let not_empty = |list: Vec<String>| {
+        list.into_iter()
+            .map(|entry| entry.trim().to_owned())
+            .filter(|entry| !entry.is_empty())
+            .collect::<Vec<String>>()
+    };
+
+    IniModLists {
+        workshop_items: not_empty(current_workshop_items),
+        mods: not_empty(current_mods),
+        maps: not_empty(current_maps),
+    }
 }
 
 